@@ -62,11 +62,7 @@ static SETTINGS: OnceLock<Settings> = OnceLock::new();
  */
 pub fn get_settings(location_opt: Option<&str>) -> &'static Settings {
     SETTINGS.get_or_init(|| {
-        let path = if let Some(location) = location_opt {
-            location
-        }else{
-            "fastdfs.conf"
-        };
+        let path = location_opt.unwrap_or("fastdfs.conf");
         load_settings(path).unwrap()
     })
 }
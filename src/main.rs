@@ -10,7 +10,7 @@ async fn main() -> Result<(), io::Error>{
     // get_settings(Some("/data/jar/police_data_deal_bin/fastdfs.conf"));
     let location = "C:\\Users\\dlgump\\Desktop\\国际摩尔斯电码.svg.png";
     let path = Path::new(location);
-    let file_ext_name = path.extension().unwrap().to_str().unwrap().to_string();
+    let _file_ext_name = path.extension().unwrap().to_str().unwrap().to_string();
     let file = tokio::fs::read(path).await?;
 
     println!("{:?}",StorageClient::upload_file(&file, "jpg").await?);
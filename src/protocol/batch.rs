@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+use crate::protocol::pool::connection_pool::discard_connection;
+use crate::protocol::proto_common;
+use crate::protocol::storage_client::StorageClient;
+use crate::protocol::storage_server::StorageServer;
+use crate::protocol::tracker_server::TrackerServer;
+
+/// 批量上传的文件来源
+#[derive(Debug, Clone)]
+pub enum UploadSource {
+    /// 内存中的文件内容
+    Bytes(Vec<u8>),
+    /// 本地文件路径
+    Path(PathBuf),
+}
+
+/// 批量删除结果，各列表中的文件ID保持输入顺序
+#[derive(Debug, Default)]
+pub struct BatchDeleteResult {
+    /// 删除成功的文件ID
+    pub deleted: Vec<String>,
+    /// storage_server返回文件不存在（ENOENT）的文件ID
+    pub not_found: Vec<String>,
+    /// 删除失败的文件ID及失败原因
+    pub failed: Vec<(String, io::Error)>,
+}
+
+/// 文件ID及其在输入中的序号
+type IndexedFileId = (usize, String);
+
+/// 单个文件的删除结果，只有storage_server删除时返回ENOENT才计为NotFound，
+/// tracker_server查询阶段返回的ENOENT表示group或源storage_server不存在，计为Failed
+enum DeleteOutcome {
+    Deleted,
+    NotFound,
+    Failed(io::Error),
+}
+
+impl From<Result<(), io::Error>> for DeleteOutcome {
+    fn from(result: Result<(), io::Error>) -> Self {
+        match result {
+            Ok(()) => DeleteOutcome::Deleted,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DeleteOutcome::NotFound,
+            Err(e) => DeleteOutcome::Failed(e),
+        }
+    }
+}
+
+
+impl StorageClient {
+    /**
+     * 批量上传文件，最多同时执行concurrency个上传，返回结果与输入顺序一致，单个文件失败不影响其他文件
+     * 来源为Path且扩展名为空时，使用文件路径的扩展名
+     */
+    pub async fn upload_batch<I>(items: I, concurrency: usize) -> Vec<Result<(String,String), io::Error>>
+    where
+        I: IntoIterator<Item = (UploadSource, String)>,
+    {
+        run_batch(items, concurrency, |(source, file_ext_name)| async move {
+            match source {
+                UploadSource::Bytes(file_buff) => Self::upload_file(&file_buff, &file_ext_name).await,
+                UploadSource::Path(path) => {
+                    let file_ext_name = Some(file_ext_name.as_str()).filter(|ext_name| !ext_name.is_empty());
+                    Self::upload_path(path, file_ext_name).await
+                }
+            }
+        }).await
+    }
+
+    /**
+     * 批量删除文件，file_id格式为 group_name/remote_filename
+     * 先查询每个文件的源storage_server，再按group和源storage_server分组，
+     * 每组拆分为若干批，每批在一个连接上依次删除，各批之间最多同时执行concurrency个
+     */
+    pub async fn delete_batch<I, S>(file_ids: I, concurrency: usize) -> BatchDeleteResult
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let concurrency = concurrency.max(1);
+        let file_ids: Vec<IndexedFileId> = file_ids.into_iter().map(Into::into).enumerate().collect();
+        let mut outcomes: Vec<(usize, String, DeleteOutcome)> = Vec::with_capacity(file_ids.len());
+
+        // 查询每个文件的源storage_server
+        let storage_servers = run_batch(file_ids.clone(), concurrency, |(_, file_id)| async move {
+            let (group_name, remote_filename) = proto_common::split_file_id(&file_id)?;
+            TrackerServer::get_update_storage(group_name, remote_filename).await
+        }).await;
+
+        // 按 group_name + 源storage_server 分组
+        let mut storage_groups: HashMap<String, (StorageServer, Vec<IndexedFileId>)> = HashMap::new();
+        for ((index, file_id), storage_server) in file_ids.into_iter().zip(storage_servers) {
+            match storage_server {
+                Ok(storage_server) => {
+                    let group_name = file_id.split('/').next().unwrap_or_default();
+                    storage_groups.entry(format!("{}/{}:{}", group_name, storage_server.ip, storage_server.port))
+                        .or_insert_with(|| (storage_server, Vec::new()))
+                        .1.push((index, file_id));
+                }
+                Err(e) => outcomes.push((index, file_id, DeleteOutcome::Failed(e))),
+            }
+        }
+
+        let mut chunks = Vec::new();
+        for (storage_server, group_file_ids) in storage_groups.into_values() {
+            let chunk_size = group_file_ids.len().div_ceil(concurrency);
+            for chunk in group_file_ids.chunks(chunk_size) {
+                chunks.push((storage_server.clone(), chunk.to_vec()));
+            }
+        }
+        let chunk_file_ids: Vec<Vec<IndexedFileId>> = chunks.iter().map(|(_, chunk)| chunk.clone()).collect();
+        let chunk_results = run_batch(chunks, concurrency, |(storage_server, chunk)| async move {
+            Ok(Self::delete_files_from_storage(&storage_server, chunk).await)
+        }).await;
+        for (chunk, chunk_result) in chunk_file_ids.into_iter().zip(chunk_results) {
+            match chunk_result {
+                Ok(chunk_outcomes) => outcomes.extend(chunk_outcomes.into_iter().map(|(index, file_id, outcome)| (index, file_id, outcome.into()))),
+                Err(e) => {
+                    for (index, file_id) in chunk {
+                        outcomes.push((index, file_id, DeleteOutcome::Failed(io::Error::new(e.kind(), e.to_string()))));
+                    }
+                }
+            }
+        }
+
+        outcomes.sort_by_key(|(index, _, _)| *index);
+        let mut result = BatchDeleteResult::default();
+        for (_, file_id, outcome) in outcomes {
+            match outcome {
+                DeleteOutcome::Deleted => result.deleted.push(file_id),
+                DeleteOutcome::NotFound => result.not_found.push(file_id),
+                DeleteOutcome::Failed(e) => result.failed.push((file_id, e)),
+            }
+        }
+        result
+    }
+
+    /**
+     * 在同一storage_server上依次删除文件，连接出错时丢弃该连接，后续文件重新获取连接
+     */
+    async fn delete_files_from_storage(storage_server: &StorageServer, file_ids: Vec<IndexedFileId>) -> Vec<(usize, String, Result<(), io::Error>)> {
+        let mut outcomes = Vec::with_capacity(file_ids.len());
+        let mut connection = None;
+        for (index, file_id) in file_ids {
+            let mut stream = match connection.take() {
+                Some(stream) => stream,
+                None => match Self::get_storage_connection(storage_server).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        outcomes.push((index, file_id, Err(e)));
+                        continue;
+                    }
+                },
+            };
+            let (group_name, remote_filename) = proto_common::split_file_id(&file_id).unwrap_or_default();
+            let header = match Self::send_package(&mut stream, proto_common::STORAGE_PROTO_CMD_DELETE_FILE, group_name, remote_filename).await {
+                Ok(()) => proto_common::recv_header(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, Some(0)).await,
+                Err(e) => Err(e),
+            };
+            match header {
+                Ok(header) => {
+                    let outcome = if header.errno == 0 { Ok(()) } else { Err(proto_common::errno_error("storage_server", header.errno)) };
+                    outcomes.push((index, file_id, outcome));
+                    connection = Some(stream);
+                }
+                Err(e) => {
+                    discard_connection(stream);
+                    outcomes.push((index, file_id, Err(e)));
+                }
+            }
+        }
+        outcomes
+    }
+}
+
+
+/**
+ * 以最多concurrency个并发任务执行f，返回结果与items顺序一致，任务panic时对应结果为Err
+ */
+pub(crate) async fn run_batch<I, F, Fut, T>(items: I, concurrency: usize, f: F) -> Vec<Result<T, io::Error>>
+where
+    I: IntoIterator,
+    F: Fn(I::Item) -> Fut,
+    Fut: Future<Output = Result<T, io::Error>> + Send + 'static,
+    T: Send + 'static,
+{
+    let concurrency = concurrency.max(1);
+    let mut join_set = JoinSet::new();
+    let mut task_indexes = HashMap::new();
+    let mut results = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        if join_set.len() >= concurrency {
+            join_next(&mut join_set, &mut task_indexes, &mut results).await;
+        }
+        results.push(None);
+        let handle = join_set.spawn(f(item));
+        task_indexes.insert(handle.id(), index);
+    }
+    while !join_set.is_empty() {
+        join_next(&mut join_set, &mut task_indexes, &mut results).await;
+    }
+    results.into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(io::Error::other("批量任务未执行"))))
+        .collect()
+}
+
+async fn join_next<T: 'static>(join_set: &mut JoinSet<Result<T, io::Error>>, task_indexes: &mut HashMap<tokio::task::Id, usize>, results: &mut [Option<Result<T, io::Error>>]) {
+    let (id, result) = match join_set.join_next_with_id().await {
+        Some(Ok((id, result))) => (id, result),
+        Some(Err(e)) => (e.id(), Err(io::Error::other(format!("批量任务执行异常:{}", e)))),
+        None => return,
+    };
+    if let Some(index) = task_indexes.remove(&id) {
+        results[index] = Some(result);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_run_batch() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let results = run_batch(0..20u64, 3, |i| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            async move {
+                let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(current, Ordering::SeqCst);
+                // 让后面的任务先完成，验证结果仍按输入顺序返回
+                tokio::time::sleep(Duration::from_millis(20 - i)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                match i {
+                    5 => Err(io::Error::other("failed")),
+                    7 => panic!("panicked"),
+                    _ => Ok(i),
+                }
+            }
+        }).await;
+
+        assert_eq!(results.len(), 20);
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+        for (i, result) in results.iter().enumerate() {
+            match i {
+                5 | 7 => assert!(result.is_err()),
+                _ => assert_eq!(*result.as_ref().unwrap(), i as u64),
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::task::JoinSet;
+use crate::protocol::pool::connection_pool::discard_connection;
+use crate::protocol::proto_common;
+use crate::protocol::storage_client::StorageClient;
+use crate::protocol::storage_server::StorageServer;
+use crate::protocol::tracker_server::TrackerServer;
+
+/// 下载时CRC32的比对来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crc32Verify {
+    /// 通过QUERY_FILE_INFO获取storage_server计算的CRC32
+    QueryFileInfo,
+    /// 使用文件名中记录的CRC32，appender文件、从文件无法解析时退回到QUERY_FILE_INFO
+    FileId,
+}
+
+/// 下载内容的CRC32与服务端不一致，通过 io::Error::get_ref 后 downcast_ref 获取
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityError {
+    pub expected_crc32: u32,
+    pub actual_crc32: u32,
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "文件CRC32校验失败，期望:{:08x}，实际:{:08x}", self.expected_crc32, self.actual_crc32)
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// 写入时同时计算CRC32
+struct Crc32Writer<'a, W> {
+    inner: &'a mut W,
+    crc32: u32,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Crc32Writer<'_, W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, io::Error>> {
+        let poll = Pin::new(&mut *self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.crc32 = proto_common::crc32(self.crc32, &buf[..n]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
+}
+
+
+impl StorageClient {
+    /**
+     * 下载整个文件并校验CRC32，不一致时返回ErrorKind::InvalidData，内部错误为IntegrityError
+     */
+    pub async fn download_file_verified(group_name: &str, remote_filename: &str, verify: Crc32Verify) -> Result<Vec<u8>, io::Error> {
+        let expected_crc32 = Self::get_expected_crc32(group_name, remote_filename, verify).await?;
+        let file_buff = Self::download_file(group_name, remote_filename).await?;
+        Self::check_crc32(expected_crc32, proto_common::crc32(0, &file_buff))?;
+        Ok(file_buff)
+    }
+
+    /**
+     * 流式下载整个文件到writer，同时计算CRC32并与服务端比对，返回写入的字节数
+     * 不一致时返回ErrorKind::InvalidData，内部错误为IntegrityError，此时内容已写入writer，调用方需丢弃
+     */
+    pub async fn download_file_to_writer_verified<W>(group_name: &str, remote_filename: &str, verify: Crc32Verify, writer: &mut W) -> Result<u64, io::Error>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let expected_crc32 = Self::get_expected_crc32(group_name, remote_filename, verify).await?;
+        let mut crc32_writer = Crc32Writer { inner: writer, crc32: 0 };
+        let written = Self::download_file_to_writer(group_name, remote_filename, 0, 0, &mut crc32_writer).await?;
+        Self::check_crc32(expected_crc32, crc32_writer.crc32)?;
+        Ok(written)
+    }
+
+    async fn get_expected_crc32(group_name: &str, remote_filename: &str, verify: Crc32Verify) -> Result<u32, io::Error> {
+        if verify == Crc32Verify::FileId {
+            if let Some(crc32) = proto_common::get_crc32_from_filename(remote_filename) {
+                return Ok(crc32);
+            }
+        }
+        Ok(Self::query_file_info(group_name, remote_filename, true).await?.crc32)
+    }
+
+    fn check_crc32(expected_crc32: u32, actual_crc32: u32) -> Result<(), io::Error> {
+        if expected_crc32 != actual_crc32 {
+            return Err(io::Error::new(ErrorKind::InvalidData, IntegrityError { expected_crc32, actual_crc32 }));
+        }
+        Ok(())
+    }
+
+
+    /**
+     * 下载文件到本地路径，file_id格式为 group_name/remote_filename，返回文件大小
+     * 先写入同目录下的临时文件，fsync后重命名为dest；临时文件已存在时从其长度处继续下载
+     * 临时文件旁的 .id 文件记录对应的file_id，与本次file_id不一致时丢弃临时文件从头下载
+     */
+    pub async fn download_to_path<P>(file_id: &str, dest: P) -> Result<u64, io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let dest = dest.as_ref();
+        let (group_name, remote_filename) = proto_common::split_file_id(file_id)?;
+        let temp_path = Self::get_download_temp_path(dest)?;
+        let id_path = Self::get_download_id_path(&temp_path);
+        let mut temp_file = OpenOptions::new().create(true).append(true).open(&temp_path).await?;
+        let mut file_offset = temp_file.metadata().await?.len();
+
+        if file_offset > 0 {
+            let temp_file_id = match tokio::fs::read(&id_path).await {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            if temp_file_id.as_deref() != Some(file_id.as_bytes()) {
+                // 临时文件属于其他file_id（或来源未知），不能续传
+                temp_file.set_len(0).await?;
+                file_offset = 0;
+            }
+        }
+        if file_offset == 0 {
+            // 写入数据前先记录file_id并fsync，保证续传时临时文件内容一定属于记录的file_id
+            let mut id_file = tokio::fs::File::create(&id_path).await?;
+            id_file.write_all(file_id.as_bytes()).await?;
+            id_file.sync_all().await?;
+        }
+
+        if file_offset > 0 {
+            let file_info = Self::query_file_info(group_name, remote_filename, false).await?;
+            if file_offset > file_info.file_size {
+                // 临时文件比服务端文件还大，说明不是同一个文件，从头下载
+                temp_file.set_len(0).await?;
+                file_offset = 0;
+            } else if file_offset == file_info.file_size {
+                return Self::finish_download(temp_file, &temp_path, &id_path, dest, file_offset).await;
+            }
+        }
+
+        let written = Self::download_file_to_writer(group_name, remote_filename, file_offset, 0, &mut temp_file).await?;
+        Self::finish_download(temp_file, &temp_path, &id_path, dest, file_offset + written).await
+    }
+
+    /**
+     * 从group内所有可下载该文件的storage_server并行下载，文件按chunk_size拆分为多个区间，最多同时下载concurrency个区间
+     * 区间按顺序写入writer，某个区间下载失败时依次换其他storage_server重试，返回写入的字节数
+     * 内存中最多缓存concurrency个区间
+     */
+    pub async fn download_file_parallel<W>(group_name: &str, remote_filename: &str, chunk_size: u64, concurrency: usize, writer: &mut W) -> Result<u64, io::Error>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if chunk_size == 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "chunk_size不能为0"));
+        }
+        let concurrency = concurrency.max(1);
+        let file_info = Self::query_file_info(group_name, remote_filename, false).await?;
+        let storage_servers = Arc::new(TrackerServer::get_fetch_storages(group_name, remote_filename).await?);
+        let group_name: Arc<str> = Arc::from(group_name);
+        let remote_filename: Arc<str> = Arc::from(remote_filename);
+
+        let ranges: Vec<(u64, u64)> = (0..file_info.file_size.div_ceil(chunk_size))
+            .map(|i| {
+                let file_offset = i * chunk_size;
+                (file_offset, chunk_size.min(file_info.file_size - file_offset))
+            })
+            .collect();
+
+        // 已启动但还未写入writer的区间数不超过concurrency
+        let mut join_set = JoinSet::new();
+        let mut finished_chunks = BTreeMap::new();
+        let mut next_spawn_index = 0;
+        let mut next_write_index = 0;
+        while next_write_index < ranges.len() {
+            while next_spawn_index < ranges.len() && next_spawn_index < next_write_index + concurrency {
+                let (file_offset, download_bytes) = ranges[next_spawn_index];
+                let chunk_index = next_spawn_index;
+                let storage_servers = storage_servers.clone();
+                let group_name = group_name.clone();
+                let remote_filename = remote_filename.clone();
+                join_set.spawn(async move {
+                    let result = Self::download_range_with_retry(&storage_servers, chunk_index, &group_name, &remote_filename, file_offset, download_bytes).await;
+                    (chunk_index, result)
+                });
+                next_spawn_index += 1;
+            }
+            match join_set.join_next().await {
+                Some(Ok((chunk_index, Ok(chunk)))) => {
+                    finished_chunks.insert(chunk_index, chunk);
+                }
+                Some(Ok((chunk_index, Err(e)))) => {
+                    let (file_offset, download_bytes) = ranges[chunk_index];
+                    return Err(io::Error::new(e.kind(), format!("区间 {}-{} 在所有storage_server下载均失败:{}", file_offset, file_offset + download_bytes, e)));
+                }
+                Some(Err(e)) => return Err(io::Error::other(format!("区间下载任务执行异常:{}", e))),
+                None => break,
+            }
+            while let Some(chunk) = finished_chunks.remove(&next_write_index) {
+                writer.write_all(&chunk).await?;
+                next_write_index += 1;
+            }
+        }
+        writer.flush().await?;
+        Ok(file_info.file_size)
+    }
+
+    /**
+     * 从第start_index个storage_server开始依次尝试下载指定区间，全部失败时返回最后一次的错误
+     */
+    async fn download_range_with_retry(storage_servers: &[StorageServer], start_index: usize, group_name: &str, remote_filename: &str, file_offset: u64, download_bytes: u64) -> Result<Vec<u8>, io::Error> {
+        let mut last_err = io::Error::new(ErrorKind::NotFound, "无可用storage_server");
+        for i in 0..storage_servers.len() {
+            let storage_server = &storage_servers[(start_index + i) % storage_servers.len()];
+            match Self::download_range(storage_server, group_name, remote_filename, file_offset, download_bytes).await {
+                Ok(chunk) => return Ok(chunk),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    /**
+     * 从指定storage_server下载一个区间
+     */
+    async fn download_range(storage_server: &StorageServer, group_name: &str, remote_filename: &str, file_offset: u64, download_bytes: u64) -> Result<Vec<u8>, io::Error> {
+        let mut stream = Self::get_storage_connection(storage_server).await?;
+        Self::send_download_package(&mut stream, group_name, remote_filename, file_offset, download_bytes).await?;
+        let header = proto_common::recv_header(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, Some(download_bytes as usize)).await;
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => {
+                discard_connection(stream);
+                return Err(e);
+            }
+        };
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("storage_server", header.errno));
+        }
+        let mut chunk = vec![0u8; header.body_len];
+        if let Err(e) = stream.read_exact(&mut chunk).await {
+            discard_connection(stream);
+            return Err(e);
+        }
+        Ok(chunk)
+    }
+
+    /**
+     * 临时文件与目标文件位于同一目录，保证重命名是原子操作
+     */
+    fn get_download_temp_path(dest: &Path) -> Result<PathBuf, io::Error> {
+        let file_name = dest.file_name()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, format!("{} 不是文件路径", dest.display())))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(".fdfs-download");
+        Ok(dest.with_file_name(temp_name))
+    }
+
+    /**
+     * 记录临时文件对应file_id的文件路径
+     */
+    fn get_download_id_path(temp_path: &Path) -> PathBuf {
+        let mut id_path = temp_path.as_os_str().to_owned();
+        id_path.push(".id");
+        PathBuf::from(id_path)
+    }
+
+    async fn finish_download(temp_file: tokio::fs::File, temp_path: &Path, id_path: &Path, dest: &Path, file_size: u64) -> Result<u64, io::Error> {
+        temp_file.sync_all().await?;
+        drop(temp_file);
+        tokio::fs::rename(temp_path, dest).await?;
+        // dest已就绪，残留的 .id 文件会在下次下载时被覆盖，删除失败不影响结果
+        let _ = tokio::fs::remove_file(id_path).await;
+        // 同步目录，保证重命名在崩溃后仍然有效
+        #[cfg(unix)]
+        if let Some(dir) = dest.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::File::open(dir).await?.sync_all().await?;
+        }
+        Ok(file_size)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_crc32_writer() {
+        let mut buff = Vec::new();
+        let mut crc32_writer = Crc32Writer { inner: &mut buff, crc32: 0 };
+        crc32_writer.write_all(b"1234").await.unwrap();
+        crc32_writer.write_all(b"56789").await.unwrap();
+        assert_eq!(crc32_writer.crc32, 0xCBF4_3926);
+        assert_eq!(buff, b"123456789");
+
+        let err = StorageClient::check_crc32(0xCBF4_3926, 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let integrity_error = err.get_ref().and_then(|e| e.downcast_ref::<IntegrityError>()).unwrap();
+        assert_eq!(integrity_error.actual_crc32, 0);
+    }
+
+    #[test]
+    fn test_get_download_temp_path() {
+        assert_eq!(StorageClient::get_download_temp_path(Path::new("/data/cache/video.mp4")).unwrap(), Path::new("/data/cache/.video.mp4.fdfs-download"));
+        assert_eq!(StorageClient::get_download_temp_path(Path::new("video.mp4")).unwrap(), Path::new(".video.mp4.fdfs-download"));
+        assert!(StorageClient::get_download_temp_path(Path::new("/")).is_err());
+        assert_eq!(StorageClient::get_download_id_path(Path::new("/data/cache/.video.mp4.fdfs-download")), Path::new("/data/cache/.video.mp4.fdfs-download.id"));
+    }
+}
@@ -0,0 +1,23 @@
+
+/// 文件信息，由 STORAGE_PROTO_CMD_QUERY_FILE_INFO 返回
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    pub file_size: u64,
+    /// 文件创建时间，unix时间戳（秒）
+    pub create_timestamp: u64,
+    /// 查询时未计算CRC32的，该值为0
+    pub crc32: u32,
+    pub source_ip_addr: String,
+}
+
+
+impl FileInfo {
+    pub fn new(file_size: u64, create_timestamp: u64, crc32: u32, source_ip_addr: &str) -> Self {
+        FileInfo {
+            file_size,
+            create_timestamp,
+            crc32,
+            source_ip_addr: source_ip_addr.to_string(),
+        }
+    }
+}
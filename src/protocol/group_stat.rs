@@ -0,0 +1,46 @@
+use crate::protocol::proto_common;
+use crate::protocol::proto_common::FieldReader;
+
+/// group统计信息，由 TRACKER_PROTO_CMD_SERVER_LIST_ALL_GROUPS 返回
+#[derive(Debug, Clone)]
+pub struct GroupStat {
+    pub group_name: String,
+    pub total_mb: u64,
+    pub free_mb: u64,
+    pub trunk_free_mb: u64,
+    /// storage_server数量
+    pub storage_count: u32,
+    pub storage_port: u16,
+    pub storage_http_port: u16,
+    /// 状态为ACTIVE的storage_server数量
+    pub active_count: u32,
+    /// 当前上传使用的storage_server序号
+    pub current_write_server: u32,
+    pub store_path_count: u32,
+    pub subdir_count_per_path: u32,
+    pub current_trunk_file_id: u32,
+}
+
+
+impl GroupStat {
+    /// 单条记录的长度：group_name(FDFS_GROUP_NAME_MAX_LEN + 1) + 11个8字节整数
+    pub const RECORD_SIZE: usize = 16 + 1 + 11 * 8;
+
+    pub(crate) fn from_bytes(bs: &[u8]) -> Self {
+        let mut reader = FieldReader::new(bs);
+        GroupStat {
+            group_name: reader.read_string(proto_common::FDFS_GROUP_NAME_MAX_LEN as usize + 1),
+            total_mb: reader.read_long(),
+            free_mb: reader.read_long(),
+            trunk_free_mb: reader.read_long(),
+            storage_count: reader.read_long() as u32,
+            storage_port: reader.read_long() as u16,
+            storage_http_port: reader.read_long() as u16,
+            active_count: reader.read_long() as u32,
+            current_write_server: reader.read_long() as u32,
+            store_path_count: reader.read_long() as u32,
+            subdir_count_per_path: reader.read_long() as u32,
+            current_trunk_file_id: reader.read_long() as u32,
+        }
+    }
+}
@@ -1,5 +1,13 @@
 pub mod pool;
 pub mod tracker_server;
+pub mod tracker_admin;
 pub mod proto_common;
 pub mod storage_server;
-pub mod storage_client;
\ No newline at end of file
+pub mod storage_client;
+pub mod file_info;
+pub mod upload_options;
+pub mod batch;
+pub mod download;
+pub mod resumable_upload;
+pub mod group_stat;
+pub mod storage_stat;
\ No newline at end of file
@@ -29,7 +29,7 @@ impl Manager for TcpManager {
     type Error = Error;
 
     async fn create(&self) -> Result<TcpStream, Error> {
-        /// 创建 TCP 连接时，通过 timeout 限制建立连接的时间
+        // 创建 TCP 连接时，通过 timeout 限制建立连接的时间
         let stream = timeout(self.connection_timeout, TcpStream::connect(&self.target)).await??;
         Ok(stream)
     }
@@ -44,7 +44,7 @@ impl Manager for TcpManager {
         if info.errno == 0u8 {
             Ok(())
         } else {
-            Err(RecycleError::from(io::Error::new(io::ErrorKind::Other, "连接已失效")))
+            Err(RecycleError::from(io::Error::other("连接已失效")))
         }
     }
 }
\ No newline at end of file
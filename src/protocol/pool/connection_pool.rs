@@ -1,9 +1,11 @@
 use crate::protocol::pool::connection_manager::TcpManager;
 use dashmap::DashMap;
-use deadpool::managed::{Manager, Object, Pool, PoolConfig, PoolError, Timeouts};
+use deadpool::managed::{Object, Pool, PoolConfig, PoolError, Timeouts};
 use std::io::Error;
+use std::sync::OnceLock;
 use deadpool_runtime::Runtime;
 use tokio::time::Duration;
+use crate::config::settings;
 
 
 static CONNECTION_POOL2: OnceLock<MultiTargetPool> = OnceLock::new();
@@ -24,6 +26,14 @@ pub fn get_connection_pool() -> &'static MultiTargetPool {
     })
 }
 
+/**
+ * 请求未发送完整或响应未读取完整时，连接中残留的数据会被下一个使用者读到，
+ * 直接从连接池中移除该连接，不再归还
+ */
+pub fn discard_connection(connection: Object<TcpManager>) {
+    drop(Object::take(connection));
+}
+
 
 
 
@@ -33,6 +43,8 @@ pub struct MultiTargetPool {
     pub pools: DashMap<String, Pool<TcpManager>>,
     /// 连接池的全局配置
     idle_timeout: Option<Duration>,
+    // deadpool 0.12 不支持连接最大生命周期，暂未使用
+    #[allow(dead_code)]
     max_lifetime: Option<Duration>,
     connection_timeout: Duration,
     max_size: usize,
@@ -70,7 +82,6 @@ impl MultiTargetPool {
                 wait: self.idle_timeout,
                 create: Some(self.connection_timeout),
                 recycle: Some(Duration::from_secs(10)),
-                ..Default::default()
             },
             ..Default::default()
         };
@@ -87,82 +98,66 @@ impl MultiTargetPool {
 }
 
 
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-use std::io;
-use std::sync::{Arc, OnceLock};
-use crate::config::settings;
-
-#[tokio::test]
-async fn test() -> Result<(), io::Error> {
-    // 构造 MultiTargetPool，设置每个池最大连接数为 8，
-    // 连接建立超时为 5 秒，闲置超时为 60 秒，最大生命周期为 300 秒
-    let multi_pool = Arc::new(MultiTargetPool::new(
-        8,
-        Duration::from_secs(5),
-        Some(Duration::from_secs(60)),
-        Some(Duration::from_secs(300)),
-    ));
-
-    // 示例目标地址列表
-    let targets = vec!["127.0.0.1:80"
-                       // , "127.0.0.1:8001"
-                       // , "127.0.0.1:8002"
-    ];
-
-    let mut handles = vec![];
-    for i in 0..10{
-        for target in targets.clone() {
-            let pool = multi_pool.clone();
-            let target = target.to_string();
-            let handle = tokio::spawn(async move {
-                match pool.get_connection(&target).await {
-                    Ok(conn) => {
-                        println!("获得目标{} {} 的连接",i, target);
-                        // 使用连接进行一些操作，这里仅示例获取后即归还（deadpool 自动归还连接）
-                        drop(conn);
-                        // sleep(Duration::from_secs(2))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test() -> Result<(), io::Error> {
+        // 构造 MultiTargetPool，设置每个池最大连接数为 8，
+        // 连接建立超时为 5 秒，闲置超时为 60 秒，最大生命周期为 300 秒
+        let multi_pool = Arc::new(MultiTargetPool::new(
+            8,
+            Duration::from_secs(5),
+            Some(Duration::from_secs(60)),
+            Some(Duration::from_secs(300)),
+        ));
+
+        // 示例目标地址列表
+        let targets = vec!["127.0.0.1:80"
+                           // , "127.0.0.1:8001"
+                           // , "127.0.0.1:8002"
+        ];
+
+        let mut handles = vec![];
+        for i in 0..10{
+            for target in targets.clone() {
+                let pool = multi_pool.clone();
+                let target = target.to_string();
+                let handle = tokio::spawn(async move {
+                    match pool.get_connection(&target).await {
+                        Ok(conn) => {
+                            println!("获得目标{} {} 的连接",i, target);
+                            // 使用连接进行一些操作，这里仅示例获取后即归还（deadpool 自动归还连接）
+                            drop(conn);
+                            // sleep(Duration::from_secs(2))
+                        }
+                        Err(e) => {
+                            eprintln!("连接 {} 失败：{}", target, e);
+                        }
                     }
-                    Err(e) => {
-                        eprintln!("连接 {} 失败：{}", target, e);
-                    }
-                }
-            });
-            handles.push(handle);
+                });
+                handles.push(handle);
+            }
         }
-    }
 
 
-    for handle in handles {
-        handle.await.unwrap();
-    }
+        for handle in handles {
+            handle.await.unwrap();
+        }
 
-    // 输出各目标连接池当前的连接数量
-    for entry in multi_pool.pools.iter() {
-        println!(
-            "目标 {} 的连接池中有 {} 个连接，最大连接数为{}",
-            entry.key(),
-            entry.value().status().size,
-            entry.value().status().max_size
-        );
-    }
+        // 输出各目标连接池当前的连接数量
+        for entry in multi_pool.pools.iter() {
+            println!(
+                "目标 {} 的连接池中有 {} 个连接，最大连接数为{}",
+                entry.key(),
+                entry.value().status().size,
+                entry.value().status().max_size
+            );
+        }
 
-    Ok(())
+        Ok(())
+    }
 }
-
-
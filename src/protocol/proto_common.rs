@@ -1,9 +1,29 @@
 use crate::protocol::pool::connection_manager::TcpManager;
 use deadpool::managed::Object;
+use encoding_rs::Encoding;
+use std::collections::HashMap;
 use std::io;
 use tokio::io::AsyncReadExt;
 use tokio::net::TcpStream;
 
+pub static TRACKER_PROTO_CMD_SERVER_LIST_ALL_GROUPS:u8 = 91;
+
+pub static TRACKER_PROTO_CMD_SERVER_LIST_STORAGE:u8 = 92;
+
+pub static TRACKER_PROTO_CMD_SERVER_DELETE_STORAGE:u8 = 93;
+
+pub static TRACKER_PROTO_CMD_SERVER_SET_TRUNK_SERVER:u8 = 94;
+
+pub static TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITHOUT_GROUP_ONE:u8 = 101;
+
+pub static TRACKER_PROTO_CMD_SERVICE_QUERY_FETCH_ONE:u8 = 102;
+
+pub static TRACKER_PROTO_CMD_SERVICE_QUERY_UPDATE:u8 = 103;
+
+pub static TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITH_GROUP_ONE:u8 = 104;
+
+pub static TRACKER_PROTO_CMD_SERVICE_QUERY_FETCH_ALL:u8 = 105;
+
 pub static TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITHOUT_GROUP_ALL:u8 = 106;
 
 pub static TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITH_GROUP_ALL:u8 = 107;
@@ -20,18 +40,83 @@ pub static TRACKER_PROTO_CMD_RESP:u8 = 100;
 
 pub static FDFS_IPADDR_SIZE:usize = 46;
 
+pub static FDFS_STORAGE_ID_MAX_SIZE:usize = 16;
+
+pub static FDFS_DOMAIN_NAME_MAX_SIZE:usize = 128;
+
+pub static FDFS_VERSION_SIZE:usize = 6;
+
 
 pub static STORAGE_PROTO_CMD_UPLOAD_FILE:u8 = 11;
 
+pub static STORAGE_PROTO_CMD_DELETE_FILE:u8 = 12;
+
+pub static STORAGE_PROTO_CMD_SET_METADATA:u8 = 13;
+
+pub static STORAGE_PROTO_CMD_DOWNLOAD_FILE:u8 = 14;
+
+pub static STORAGE_PROTO_CMD_GET_METADATA:u8 = 15;
+
+pub static STORAGE_PROTO_CMD_UPLOAD_SLAVE_FILE:u8 = 21;
+
+pub static STORAGE_PROTO_CMD_QUERY_FILE_INFO:u8 = 22;
+
+pub static STORAGE_PROTO_CMD_UPLOAD_APPENDER_FILE:u8 = 23;
+
+pub static STORAGE_PROTO_CMD_APPEND_FILE:u8 = 24;
+
+pub static STORAGE_PROTO_CMD_MODIFY_FILE:u8 = 34;
+
+pub static STORAGE_PROTO_CMD_TRUNCATE_FILE:u8 = 36;
+
+pub static STORAGE_PROTO_CMD_REGENERATE_APPENDER_FILENAME:u8 = 38;
+
+/// QUERY_FILE_INFO 请求头状态位：不计算CRC32
+pub static FDFS_QUERY_FINFO_FLAGS_NOT_CALC_CRC32:u8 = 1;
+
 pub static FDFS_FILE_EXT_NAME_MAX_LEN:usize = 6;
 
+pub static FDFS_FILE_PREFIX_MAX_LEN:usize = 16;
+
+/// 文件名中存储路径部分的长度，如 M00/00/00/
+pub static FDFS_FILE_PATH_LEN:usize = 10;
+
+/// 文件名中base64编码部分的长度
+pub static FDFS_FILENAME_BASE64_LENGTH:usize = 27;
+
+pub static FDFS_TRUNK_FILE_INFO_LEN:usize = 16;
+
+pub static NORMAL_LOGIC_FILENAME_LENGTH:usize = FDFS_FILE_PATH_LEN + FDFS_FILENAME_BASE64_LENGTH + FDFS_FILE_EXT_NAME_MAX_LEN + 1;
+
+pub static TRUNK_LOGIC_FILENAME_LENGTH:usize = NORMAL_LOGIC_FILENAME_LENGTH + FDFS_TRUNK_FILE_INFO_LEN;
+
+/// 文件名中记录的文件大小包含该位时为appender文件
+pub static APPENDER_FILE_SIZE:u64 = 256 * 1024 * 1024 * 1024 * 1024 * 1024;
+
+/// 文件名中记录的文件大小包含该位时为trunk文件
+pub static TRUNK_FILE_MARK_SIZE:u64 = 512 * 1024 * 1024 * 1024 * 1024 * 1024;
+
 
 pub static TRACKER_QUERY_STORAGE_STORE_BODY_LEN:usize = FDFS_GROUP_NAME_MAX_LEN as usize + FDFS_IPADDR_SIZE + FDFS_PROTO_PKG_LEN_SIZE;
 
+pub static TRACKER_QUERY_STORAGE_FETCH_BODY_LEN:usize = FDFS_GROUP_NAME_MAX_LEN as usize + FDFS_IPADDR_SIZE - 1 + FDFS_PROTO_PKG_LEN_SIZE;
+
 pub static STORAGE_PROTO_CMD_RESP:u8 = TRACKER_PROTO_CMD_RESP;
 
 pub static FDFS_PROTO_CMD_ACTIVE_TEST:u8 = 111;
 
+pub static ERR_NO_ENOENT:u8 = 2;
+
+/// 覆盖原有元数据
+pub static STORAGE_SET_METADATA_FLAG_OVERWRITE:u8 = b'O';
+
+/// 与原有元数据合并，同名的覆盖
+pub static STORAGE_SET_METADATA_FLAG_MERGE:u8 = b'M';
+
+pub static FDFS_RECORD_SEPERATOR:u8 = 0x01;
+
+pub static FDFS_FIELD_SEPERATOR:u8 = 0x02;
+
 
 
 
@@ -51,6 +136,214 @@ pub fn pack_header(cmd:u8, pkg_len: u64, errno: u8) -> Vec<u8>{
 }
 
 
+/**
+ * 将服务端返回的errno转换为io::Error，ENOENT转换为NotFound，其余为Other
+ */
+pub fn errno_error(server: &str, errno: u8) -> io::Error {
+    if errno == ERR_NO_ENOENT {
+        io::Error::new(io::ErrorKind::NotFound, format!("{}返回错误:{}，文件不存在", server, errno))
+    } else {
+        io::Error::other(format!("{}返回错误:{}", server, errno))
+    }
+}
+
+/**
+ * 根据配置的charset获取编码，不支持的charset返回InvalidInput
+ */
+pub fn get_encoding(charset: &str) -> Result<&'static Encoding, io::Error> {
+    Encoding::for_label(charset.as_bytes())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("不支持的charset:{}", charset)))
+}
+
+/**
+ * 将元数据打包为 name\x02value\x01name\x02value 格式，并按charset编码
+ */
+pub fn pack_metadata(meta_list: &HashMap<String, String>, charset: &str) -> Result<Vec<u8>, io::Error> {
+    let encoding = get_encoding(charset)?;
+    let mut meta_buff = Vec::new();
+    for (name, value) in meta_list {
+        if !meta_buff.is_empty() {
+            meta_buff.push(FDFS_RECORD_SEPERATOR);
+        }
+        meta_buff.extend_from_slice(&encoding.encode(name).0);
+        meta_buff.push(FDFS_FIELD_SEPERATOR);
+        meta_buff.extend_from_slice(&encoding.encode(value).0);
+    }
+    Ok(meta_buff)
+}
+
+/**
+ * 按charset解码并拆分storage_server返回的元数据
+ */
+pub fn split_metadata(meta_buff: &[u8], charset: &str) -> Result<HashMap<String, String>, io::Error> {
+    let encoding = get_encoding(charset)?;
+    let mut meta_list = HashMap::new();
+    for record in meta_buff.split(|b| *b == FDFS_RECORD_SEPERATOR) {
+        if record.is_empty() {
+            continue;
+        }
+        let mut fields = record.splitn(2, |b| *b == FDFS_FIELD_SEPERATOR);
+        let name = encoding.decode_without_bom_handling(fields.next().unwrap_or_default()).0.into_owned();
+        let value = encoding.decode_without_bom_handling(fields.next().unwrap_or_default()).0.into_owned();
+        meta_list.insert(name, value);
+    }
+    Ok(meta_list)
+}
+
+/**
+ * 按顺序读取tracker_server返回的定长结构体字段
+ */
+pub(crate) struct FieldReader<'a> {
+    bs: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FieldReader<'a> {
+    pub(crate) fn new(bs: &'a [u8]) -> Self {
+        FieldReader { bs, offset: 0 }
+    }
+
+    /// 读取len字节的字符串，去掉末尾的\0
+    pub(crate) fn read_string(&mut self, len: usize) -> String {
+        let value = String::from_utf8_lossy(&self.bs[self.offset..self.offset + len]).trim_end_matches('\0').to_string();
+        self.offset += len;
+        value
+    }
+
+    /// 读取8字节的整数（big-endian）
+    pub(crate) fn read_long(&mut self) -> u64 {
+        let value = buff2long(self.bs, self.offset);
+        self.offset += FDFS_PROTO_PKG_LEN_SIZE;
+        value
+    }
+
+    /// 读取4字节的整数（big-endian）
+    pub(crate) fn read_int(&mut self) -> u32 {
+        let bs = &self.bs[self.offset..self.offset + 4];
+        self.offset += 4;
+        u32::from_be_bytes([bs[0], bs[1], bs[2], bs[3]])
+    }
+
+    pub(crate) fn read_byte(&mut self) -> u8 {
+        let value = self.bs[self.offset];
+        self.offset += 1;
+        value
+    }
+}
+
+/**
+ * 将文件ID（group_name/remote_filename）拆分为 (group_name, remote_filename)
+ */
+pub fn split_file_id(file_id: &str) -> Result<(&str, &str), io::Error> {
+    match file_id.split_once('/') {
+        Some((group_name, remote_filename)) if !group_name.is_empty() && !remote_filename.is_empty() => Ok((group_name, remote_filename)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("文件ID:{} 格式不正确", file_id))),
+    }
+}
+
+static CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/**
+ * 计算CRC32（与FastDFS服务端算法一致），crc为之前数据的计算结果，首次传0，可分段计算
+ */
+pub fn crc32(crc: u32, buff: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for b in buff {
+        crc = CRC32_TABLE[((crc ^ *b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/**
+ * 按FastDFS文件名使用的base64字母表（+、/ 替换为 -、_）解码，忽略末尾不足一个字节的位
+ */
+fn decode_filename_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut buff = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for c in encoded.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            buff.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(buff)
+}
+
+/**
+ * 从文件名中解析上传时记录的CRC32
+ * appender文件、从文件的文件名中不包含自身的CRC32，返回None
+ */
+pub fn get_crc32_from_filename(remote_filename: &str) -> Option<u32> {
+    let encoded = remote_filename.get(FDFS_FILE_PATH_LEN..FDFS_FILE_PATH_LEN + FDFS_FILENAME_BASE64_LENGTH)?;
+    let buff = decode_filename_base64(encoded)?;
+    if buff.len() < 20 {
+        return None;
+    }
+    let file_size = buff2long(&buff, 8);
+    let is_slave_file = remote_filename.len() > TRUNK_LOGIC_FILENAME_LENGTH
+        || (remote_filename.len() > NORMAL_LOGIC_FILENAME_LENGTH && file_size & TRUNK_FILE_MARK_SIZE == 0);
+    if is_slave_file || file_size & APPENDER_FILE_SIZE != 0 {
+        return None;
+    }
+    Some(u32::from_be_bytes([buff[16], buff[17], buff[18], buff[19]]))
+}
+
+/**
+ * 校验group_name长度，为空或超过FDFS_GROUP_NAME_MAX_LEN字节时返回InvalidInput
+ */
+pub fn check_group_name(group_name: &str) -> Result<(), io::Error> {
+    if group_name.is_empty() || group_name.len() > FDFS_GROUP_NAME_MAX_LEN as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("group_name:{} 长度必须在1到{}字节之间", group_name, FDFS_GROUP_NAME_MAX_LEN)));
+    }
+    Ok(())
+}
+
+/**
+ * group_name 按协议补齐为 FDFS_GROUP_NAME_MAX_LEN 字节，超长部分截断
+ */
+pub fn pack_group_name(group_name: &str) -> Vec<u8> {
+    let bs = group_name.as_bytes();
+    let mut group_name_bytes = vec![0u8;FDFS_GROUP_NAME_MAX_LEN as usize];
+    let group_len = bs.len().min(FDFS_GROUP_NAME_MAX_LEN as usize);
+    group_name_bytes[..group_len].copy_from_slice(&bs[..group_len]);
+    group_name_bytes
+}
+
+/**
+ * 扩展名按协议补齐为 FDFS_FILE_EXT_NAME_MAX_LEN 字节，超长部分截断
+ */
+pub fn pack_ext_name(file_ext_name: &str) -> Vec<u8> {
+    let origin_ext_name_bs = file_ext_name.as_bytes();
+    let mut ext_name_bs = vec![0u8;FDFS_FILE_EXT_NAME_MAX_LEN];
+    let ext_name_len = origin_ext_name_bs.len().min(FDFS_FILE_EXT_NAME_MAX_LEN);
+    ext_name_bs[..ext_name_len].copy_from_slice(&origin_ext_name_bs[..ext_name_len]);
+    ext_name_bs
+}
 
 /**
  * long convert to buff (big-endian)
@@ -58,7 +351,6 @@ pub fn pack_header(cmd:u8, pkg_len: u64, errno: u8) -> Vec<u8>{
  * @param n long number
  * @return 8 bytes buff
  */
-
 pub fn long2buff(n:u64) -> Vec<u8>{
     let mut bs = vec![0u8;8];
     bs[0] = (n >> 56) as u8;
@@ -86,19 +378,22 @@ pub struct RecvHeaderInfo{
 
 pub async fn recv_package(input: &mut Object<TcpManager>,expect_cmd:u8,expect_body_len:Option<usize>) -> Result<RecvPackageInfo,io::Error>{
     let header = recv_header(input, expect_cmd, expect_body_len).await?;
-        if header.errno != 0{
-        return Err(io::Error::new(io::ErrorKind::Other,format!("recv errno: {} is not correct, expect errno: 0",header.errno)));
+    if header.errno != 0{
+        return Err(io::Error::other(format!("recv errno: {} is not correct, expect errno: 0",header.errno)));
     }
     let mut body = vec![0u8;header.body_len];
-    input.read(&mut body).await?;
+    input.read_exact(&mut body).await?;
     Ok(RecvPackageInfo { errno:0, body })
 }
 
 pub async fn recv_header(input: &mut TcpStream, expect_cmd: u8, expect_body_len: Option<usize>) -> Result<RecvHeaderInfo,io::Error>{
     let mut header = vec![0u8;FDFS_PROTO_PKG_LEN_SIZE+2];
-    let recv_len = input.read(&mut header).await?;
-    if recv_len != header.len() {
-        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,"读取头部信息失败,长度不足!"));
+    if let Err(e) = input.read_exact(&mut header).await {
+        // 只有连接提前关闭时才是长度不足，超时、连接重置等错误原样返回
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,"读取头部信息失败,长度不足!"));
+        }
+        return Err(e);
     }
 
     // 检查命令字节
@@ -118,11 +413,11 @@ pub async fn recv_header(input: &mut TcpStream, expect_cmd: u8, expect_body_len:
         return Ok(RecvHeaderInfo { errno:status, body_len: 0 });
     }
 
-        let recv_len = buff2long(&header[0..8], 0);
-        if recv_len < 0 {
+    let recv_len = buff2long(&header[0..8], 0);
+    if recv_len > i64::MAX as u64 {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
-            format!("recv body length: {} < 0!", recv_len),
+            format!("recv body length: {} < 0!", recv_len as i64),
         ));
     }
     let pkg_len = recv_len as usize;
@@ -152,3 +447,72 @@ pub fn buff2long(bs: &[u8], offset: usize) -> u64 {
         ((bs[offset + 6] as u64) << 8) |
         (bs[offset + 7] as u64)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_group_name() {
+        assert!(check_group_name("group1").is_ok());
+        assert!(check_group_name("").is_err());
+        assert!(check_group_name("group_name_too_long").is_err());
+    }
+
+    #[test]
+    fn test_metadata_pack_and_split() {
+        let mut meta_list = HashMap::new();
+        meta_list.insert("filename".to_string(), "国际摩尔斯电码.png".to_string());
+        meta_list.insert("content_type".to_string(), "image/png".to_string());
+        meta_list.insert("uploader".to_string(), String::new());
+
+        for charset in ["UTF-8", "GBK"] {
+            let meta_buff = pack_metadata(&meta_list, charset).unwrap();
+            assert_eq!(meta_buff.iter().filter(|b| **b == FDFS_RECORD_SEPERATOR).count(), 2);
+            assert_eq!(split_metadata(&meta_buff, charset).unwrap(), meta_list);
+        }
+        assert!(pack_metadata(&meta_list, "not-a-charset").is_err());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b""), 0);
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_get_crc32_from_filename() {
+        // 按storage_server的规则生成文件名：ip(4) + 创建时间(4) + 文件大小(8) + crc32(4)，base64编码
+        fn encode_filename(file_size: u64, crc32: u32, suffix: &str) -> String {
+            let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+            let mut buff = vec![192, 168, 1, 100, 0x64, 0x31, 0x2F, 0x00];
+            buff.extend_from_slice(&long2buff(file_size));
+            buff.extend_from_slice(&crc32.to_be_bytes());
+            buff.push(0);
+            let mut encoded = String::new();
+            for chunk in buff.chunks(3) {
+                let bits = (chunk[0] as u32) << 16 | (chunk[1] as u32) << 8 | chunk[2] as u32;
+                for i in 0..4 {
+                    encoded.push(alphabet[((bits >> (18 - 6 * i)) & 0x3F) as usize] as char);
+                }
+            }
+            format!("M00/00/00/{}{}", &encoded[..FDFS_FILENAME_BASE64_LENGTH], suffix)
+        }
+
+        assert_eq!(get_crc32_from_filename(&encode_filename(1024, 0xCBF4_3926, ".jpg")), Some(0xCBF4_3926));
+        assert_eq!(get_crc32_from_filename(&encode_filename(1024 | (1u64 << 58), 0xCBF4_3926, ".log")), None);
+        assert_eq!(get_crc32_from_filename(&encode_filename(1024 | (1u64 << 59), 0xCBF4_3926, "AAAAAAAAAAAAAAAA.jpg")), Some(0xCBF4_3926));
+        assert_eq!(get_crc32_from_filename(&encode_filename(1024, 0xCBF4_3926, "_150x150_thumbnail.jpg")), None);
+        assert_eq!(get_crc32_from_filename("M00/00/00/short.jpg"), None);
+    }
+
+    #[test]
+    fn test_split_file_id() {
+        assert_eq!(split_file_id("group1/M00/00/00/wKgBZGQx.jpg").unwrap(), ("group1", "M00/00/00/wKgBZGQx.jpg"));
+        assert!(split_file_id("group1").is_err());
+        assert!(split_file_id("/M00/00/00/wKgBZGQx.jpg").is_err());
+        assert!(split_file_id("group1/").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use std::io;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use crate::protocol::storage_client::StorageClient;
+use crate::protocol::tracker_server::TrackerServer;
+
+/// 断点续传检查点，每追加一块后写入本地JSON文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UploadCheckpoint {
+    pub group_name: String,
+    /// appender文件名，上传完成后会重新生成为普通文件名
+    pub appender_filename: String,
+    /// 本地文件大小，续传时用于确认是同一个文件
+    pub file_size: u64,
+    /// 已确认写入storage_server的字节数
+    pub committed_offset: u64,
+}
+
+
+impl UploadCheckpoint {
+    /**
+     * 读取检查点文件，文件不存在时返回None
+     */
+    pub async fn load<P: AsRef<Path>>(checkpoint_path: P) -> Result<Option<UploadCheckpoint>, io::Error> {
+        match tokio::fs::read(checkpoint_path).await {
+            Ok(content) => serde_json::from_slice(&content)
+                .map(Some)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("检查点文件格式不正确:{}", e))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /**
+     * 先写入临时文件再重命名，避免写入过程中崩溃导致检查点文件损坏
+     */
+    pub async fn save<P: AsRef<Path>>(&self, checkpoint_path: P) -> Result<(), io::Error> {
+        let checkpoint_path = checkpoint_path.as_ref();
+        let content = serde_json::to_vec(self).map_err(io::Error::other)?;
+        let mut temp_path = checkpoint_path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let mut temp_file = tokio::fs::File::create(&temp_path).await?;
+        temp_file.write_all(&content).await?;
+        temp_file.sync_all().await?;
+        tokio::fs::rename(&temp_path, checkpoint_path).await
+    }
+}
+
+
+impl StorageClient {
+    /**
+     * 通过appender文件分块上传本地文件，支持断点续传，返回 (group_name, remote_filename)
+     * 每追加chunk_size字节后将检查点写入checkpoint_path；checkpoint_path已存在时，
+     * 从源storage_server读取已写入的大小并从该位置继续上传。
+     * 全部上传后将appender文件重新生成为普通文件，并删除检查点文件；
+     * 检查点中的appender文件已不存在时返回NotFound，此时上传可能已经完成
+     */
+    pub async fn upload_resumable<P, C>(path: P, file_ext_name: Option<&str>, chunk_size: u64, checkpoint_path: C) -> Result<(String,String), io::Error>
+    where
+        P: AsRef<Path>,
+        C: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let checkpoint_path = checkpoint_path.as_ref();
+        if chunk_size == 0 || chunk_size > usize::MAX as u64 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("chunk_size:{} 不正确", chunk_size)));
+        }
+        let mut file = tokio::fs::File::open(path).await?;
+        let file_size = file.metadata().await?.len();
+        let mut buff = vec![0u8; chunk_size.min(file_size) as usize];
+
+        let mut checkpoint = match UploadCheckpoint::load(checkpoint_path).await? {
+            Some(mut checkpoint) => {
+                if checkpoint.file_size != file_size {
+                    return Err(io::Error::new(ErrorKind::InvalidData, format!("检查点记录的文件大小:{} 与本地文件大小:{} 不一致", checkpoint.file_size, file_size)));
+                }
+                // 以服务端实际写入的大小为准，追加完成后、写入检查点前崩溃时两者会不一致
+                // 追加总是写入源storage_server，副本可能尚未同步，因此通过QUERY_UPDATE查询源storage_server
+                let file_info = async {
+                    let storage_server = TrackerServer::get_update_storage(&checkpoint.group_name, &checkpoint.appender_filename).await?;
+                    Self::query_file_info_from_storage(&storage_server, &checkpoint.group_name, &checkpoint.appender_filename, false).await
+                }.await.map_err(|e| match e.kind() {
+                    // 重新生成文件名后、删除检查点前崩溃时，appender文件名已不存在
+                    ErrorKind::NotFound => io::Error::new(ErrorKind::NotFound, format!("appender文件:{}/{} 不存在，上传可能已经完成，请确认后删除检查点文件:{}", checkpoint.group_name, checkpoint.appender_filename, checkpoint_path.display())),
+                    _ => e,
+                })?;
+                if file_info.file_size > file_size {
+                    return Err(io::Error::new(ErrorKind::InvalidData, format!("appender文件大小:{} 超过本地文件大小:{}", file_info.file_size, file_size)));
+                }
+                checkpoint.committed_offset = file_info.file_size;
+                checkpoint
+            }
+            None => {
+                let file_ext_name = match file_ext_name {
+                    Some(ext_name) => ext_name.to_string(),
+                    None => path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_string(),
+                };
+                let len = buff.len();
+                file.read_exact(&mut buff[..len]).await?;
+                let (group_name, appender_filename) = Self::upload_appender_file(&buff[..len], &file_ext_name).await?;
+                UploadCheckpoint { group_name, appender_filename, file_size, committed_offset: len as u64 }
+            }
+        };
+        checkpoint.save(checkpoint_path).await?;
+
+        while checkpoint.committed_offset < file_size {
+            let len = buff.len().min((file_size - checkpoint.committed_offset) as usize);
+            file.seek(SeekFrom::Start(checkpoint.committed_offset)).await?;
+            file.read_exact(&mut buff[..len]).await?;
+            Self::append_file(&checkpoint.group_name, &checkpoint.appender_filename, &buff, 0, len).await?;
+            checkpoint.committed_offset += len as u64;
+            checkpoint.save(checkpoint_path).await?;
+        }
+
+        let result = Self::regenerate_appender_filename(&checkpoint.group_name, &checkpoint.appender_filename).await?;
+        tokio::fs::remove_file(checkpoint_path).await?;
+        Ok(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint_save_and_load() {
+        let checkpoint_path = std::env::temp_dir().join(format!("fastdfs-client-rs-checkpoint-{}.json", std::process::id()));
+        assert_eq!(UploadCheckpoint::load(&checkpoint_path).await.unwrap(), None);
+
+        let checkpoint = UploadCheckpoint {
+            group_name: "group1".to_string(),
+            appender_filename: "M00/00/00/wKgBZGQxAAAAAAAA.log".to_string(),
+            file_size: 1024,
+            committed_offset: 512,
+        };
+        checkpoint.save(&checkpoint_path).await.unwrap();
+        assert_eq!(UploadCheckpoint::load(&checkpoint_path).await.unwrap(), Some(checkpoint));
+
+        tokio::fs::write(&checkpoint_path, b"not json").await.unwrap();
+        assert_eq!(UploadCheckpoint::load(&checkpoint_path).await.unwrap_err().kind(), ErrorKind::InvalidData);
+        tokio::fs::remove_file(&checkpoint_path).await.unwrap();
+    }
+}
@@ -1,74 +1,813 @@
+use std::collections::HashMap;
 use std::io;
 use std::io::ErrorKind;
-use tokio::io::AsyncWriteExt;
-use crate::protocol::pool::connection_pool::{get_connection_pool};
+use std::path::Path;
+use deadpool::managed::Object;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::config::settings::get_settings;
+use crate::protocol::file_info::FileInfo;
+use crate::protocol::pool::connection_manager::TcpManager;
+use crate::protocol::pool::connection_pool::{discard_connection, get_connection_pool};
 use crate::protocol::proto_common;
-use crate::protocol::tracker_server::TrackerServer;
+use crate::protocol::storage_server::StorageServer;
+use crate::protocol::tracker_server::{StoreRouting, TrackerServer};
+use crate::protocol::upload_options::UploadOptions;
 
 pub struct StorageClient;
 
+/// 流式上传、下载时每次读写的最大字节数
+const STREAM_BUFFER_SIZE: usize = 256 * 1024;
+
+/// upload_path发送文件内容期间持有连接，未调用finish就被drop（future被取消）时关闭socket并丢弃连接，
+/// 关闭后阻塞线程中的sendfile会立即失败，storage_server收到的是不完整的请求
+#[cfg(target_os = "linux")]
+struct SendFileGuard {
+    connection: Option<Object<TcpManager>>,
+}
+
+#[cfg(target_os = "linux")]
+impl SendFileGuard {
+    fn stream(&self) -> &tokio::net::TcpStream {
+        self.connection.as_ref().expect("连接已取回")
+    }
+
+    /// 发送结束后取回连接
+    fn finish(mut self) -> Object<TcpManager> {
+        self.connection.take().expect("连接已取回")
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SendFileGuard {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            if let Ok(stream) = Object::take(connection).into_std() {
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+            }
+        }
+    }
+}
+
 
 impl StorageClient {
-    pub async fn upload_file(file_buff: &Vec<u8>, file_ext_name: &str) -> Result<(String,String), io::Error> {
-        let file_size = file_buff.len();
-        if let Ok(storage_servers) = TrackerServer::get_storage_servers(None).await {
-            if storage_servers.len() == 0{
-                return Err(io::Error::new(ErrorKind::NotFound,"无可用storage_server"));
+    pub async fn upload_file(file_buff: &[u8], file_ext_name: &str) -> Result<(String,String), io::Error> {
+        Self::do_upload_file(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, None, file_buff, file_ext_name, &UploadOptions::default()).await
+    }
+
+    /**
+     * 按上传选项上传文件，可通过options指定存储路径、查询storage_server的方式
+     */
+    pub async fn upload_file_with_options(file_buff: &[u8], file_ext_name: &str, options: &UploadOptions) -> Result<(String,String), io::Error> {
+        Self::do_upload_file(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, None, file_buff, file_ext_name, options).await
+    }
+
+    /**
+     * 上传文件到指定group，group_name为空或超过FDFS_GROUP_NAME_MAX_LEN字节时返回InvalidInput
+     */
+    pub async fn upload_file_to_group(group_name: &str, file_buff: &[u8], file_ext_name: &str) -> Result<(String,String), io::Error> {
+        Self::upload_file_to_group_with_options(group_name, file_buff, file_ext_name, &UploadOptions::default()).await
+    }
+
+    /**
+     * 按上传选项上传文件到指定group，StoreRouting::One 时使用 QUERY_STORE_WITH_GROUP_ONE
+     */
+    pub async fn upload_file_to_group_with_options(group_name: &str, file_buff: &[u8], file_ext_name: &str, options: &UploadOptions) -> Result<(String,String), io::Error> {
+        proto_common::check_group_name(group_name)?;
+        Self::do_upload_file(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, Some(group_name), file_buff, file_ext_name, options).await
+    }
+
+    /**
+     * 不经过tracker_server，直接上传文件到指定的storage_server，文件存储在storage_server.storage_path对应的存储路径下
+     */
+    pub async fn upload_file_to_storage(storage_server: &StorageServer, file_buff: &[u8], file_ext_name: &str) -> Result<(String,String), io::Error> {
+        if storage_server.ip.is_empty() || storage_server.port == 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("storage_server地址不正确:{}:{}", storage_server.ip, storage_server.port)));
+        }
+        Self::upload_file_with_storage_servers(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, std::slice::from_ref(storage_server), file_buff, file_ext_name, Some(storage_server.storage_path)).await
+    }
+
+    /**
+     * 上传appender文件，之后可通过append_file、modify_file、truncate_file修改
+     */
+    pub async fn upload_appender_file(file_buff: &[u8], file_ext_name: &str) -> Result<(String,String), io::Error> {
+        Self::do_upload_file(proto_common::STORAGE_PROTO_CMD_UPLOAD_APPENDER_FILE, None, file_buff, file_ext_name, &UploadOptions::default()).await
+    }
+
+    /**
+     * 向appender文件末尾追加 file_buff[offset..offset + length]
+     */
+    pub async fn append_file(group_name: &str, appender_filename: &str, file_buff: &[u8], offset: usize, length: usize) -> Result<(), io::Error> {
+        let append_buff = Self::check_buff_range(file_buff, offset, length)?;
+        Self::check_appender_filename(appender_filename)?;
+        let storage_server = TrackerServer::get_update_storage(group_name, appender_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+
+        let appender_filename_bs = appender_filename.as_bytes();
+        let body_len = 2 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + appender_filename_bs.len() + append_buff.len();
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_APPEND_FILE, body_len as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + body_len - append_buff.len());
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&proto_common::long2buff(appender_filename_bs.len() as u64));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(append_buff.len() as u64));
+        whole_pkg.extend_from_slice(appender_filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.write_all(append_buff).await?;
+        stream.flush().await?;
+        Self::recv_empty_response(&mut stream).await
+    }
+
+    /**
+     * 从appender文件的file_offset位置开始，用 file_buff[buffer_offset..buffer_offset + buffer_length] 覆盖原有内容
+     */
+    pub async fn modify_file(group_name: &str, appender_filename: &str, file_offset: u64, file_buff: &[u8], buffer_offset: usize, buffer_length: usize) -> Result<(), io::Error> {
+        let modify_buff = Self::check_buff_range(file_buff, buffer_offset, buffer_length)?;
+        Self::check_appender_filename(appender_filename)?;
+        Self::check_file_offset(file_offset)?;
+        let storage_server = TrackerServer::get_update_storage(group_name, appender_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+
+        let appender_filename_bs = appender_filename.as_bytes();
+        let body_len = 3 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + appender_filename_bs.len() + modify_buff.len();
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_MODIFY_FILE, body_len as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + body_len - modify_buff.len());
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&proto_common::long2buff(appender_filename_bs.len() as u64));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(file_offset));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(modify_buff.len() as u64));
+        whole_pkg.extend_from_slice(appender_filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.write_all(modify_buff).await?;
+        stream.flush().await?;
+        Self::recv_empty_response(&mut stream).await
+    }
+
+    /**
+     * 将appender文件截断为truncated_file_size字节
+     */
+    pub async fn truncate_file(group_name: &str, appender_filename: &str, truncated_file_size: u64) -> Result<(), io::Error> {
+        Self::check_appender_filename(appender_filename)?;
+        Self::check_file_offset(truncated_file_size)?;
+        let storage_server = TrackerServer::get_update_storage(group_name, appender_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+
+        let appender_filename_bs = appender_filename.as_bytes();
+        let body_len = 2 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + appender_filename_bs.len();
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_TRUNCATE_FILE, body_len as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + body_len);
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&proto_common::long2buff(appender_filename_bs.len() as u64));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(truncated_file_size));
+        whole_pkg.extend_from_slice(appender_filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
+        Self::recv_empty_response(&mut stream).await
+    }
+
+    /**
+     * 将appender文件转为普通文件，返回新的 (group_name, remote_filename)，原appender文件名失效
+     */
+    pub async fn regenerate_appender_filename(group_name: &str, appender_filename: &str) -> Result<(String,String), io::Error> {
+        Self::check_appender_filename(appender_filename)?;
+        let storage_server = TrackerServer::get_update_storage(group_name, appender_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+
+        let appender_filename_bs = appender_filename.as_bytes();
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_REGENERATE_APPENDER_FILENAME, appender_filename_bs.len() as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + appender_filename_bs.len());
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(appender_filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
+        Self::recv_upload_response(&mut stream).await
+    }
+
+    /**
+     * 检查 offset、length 是否在 file_buff 范围内，返回对应的切片
+     */
+    fn check_buff_range(file_buff: &[u8], offset: usize, length: usize) -> Result<&[u8], io::Error> {
+        match offset.checked_add(length) {
+            Some(end) if end <= file_buff.len() => Ok(&file_buff[offset..end]),
+            _ => Err(io::Error::new(ErrorKind::InvalidInput, format!("offset:{} length:{} 超出file_buff长度:{}", offset, length, file_buff.len()))),
+        }
+    }
+
+    /**
+     * 获取实际使用的存储路径序号，storage_server的存储路径数量已知时校验序号是否越界
+     * tracker_server返回的storage_server不包含存储路径数量，经过tracker_server上传时不校验
+     */
+    fn get_store_path_index(storage_server: &StorageServer, store_path_index: Option<u8>) -> Result<u8, io::Error> {
+        let Some(store_path_index) = store_path_index else {
+            return Ok(storage_server.storage_path);
+        };
+        if let Some(store_path_count) = storage_server.store_path_count {
+            if store_path_index >= store_path_count {
+                return Err(io::Error::new(ErrorKind::InvalidInput, format!("store_path_index:{} 超出storage_server {}:{} 的存储路径数量:{}", store_path_index, storage_server.ip, storage_server.port, store_path_count)));
             }
-            let random_start_index = (rand::random::<u16>() as usize) % storage_servers.len();
-            for i in 0..storage_servers.len(){
-                let index = (i + random_start_index) % storage_servers.len();
-                let storage_server = &storage_servers[index];
-                let host = storage_server.ip.to_string() +":"+ &storage_server.port.to_string();
-                let mut offset = 0;
-                if let Ok(mut stream) = get_connection_pool().get_connection(&host).await{
-                    let mut ext_name_bs = vec![0u8;proto_common::FDFS_FILE_EXT_NAME_MAX_LEN];
-                    let origin_ext_name_bs = file_ext_name.as_bytes();
-
-                    let ext_name_len = if origin_ext_name_bs.len() > proto_common::FDFS_FILE_EXT_NAME_MAX_LEN {
-                        proto_common::FDFS_FILE_EXT_NAME_MAX_LEN
-                    }else {
-                        origin_ext_name_bs.len()
-                    };
-                    ext_name_bs[..ext_name_len].copy_from_slice(&origin_ext_name_bs[..ext_name_len]);
-                    let mut size_bytes = vec![0u8;proto_common::FDFS_PROTO_PKG_LEN_SIZE + 1];
-                    let body_len = size_bytes.len() + proto_common::FDFS_FILE_EXT_NAME_MAX_LEN + file_size;
-                    size_bytes[0] = storage_server.storage_path;
-                    offset = 1;
-                    let hex_len_bytes = proto_common::long2buff(file_size as u64);
-                    size_bytes[1..1+hex_len_bytes.len()].copy_from_slice(&hex_len_bytes[..hex_len_bytes.len()]);
-                    let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, body_len as u64,0u8);
-                    let mut whole_pkg = vec![0u8; header.len()+body_len-file_size];
-                    whole_pkg[..header.len()].copy_from_slice(&header[..header.len()]);
-                    whole_pkg[header.len()..header.len()+ size_bytes.len()].copy_from_slice(&size_bytes[..size_bytes.len()]);
-                    offset = header.len() + size_bytes.len();
-                    whole_pkg[offset..offset+ext_name_bs.len()].copy_from_slice(&ext_name_bs[..ext_name_bs.len()]);
-                    stream.write_all(&whole_pkg).await?;
-                    stream.flush().await?;
-                    //&whole_pkg转为base64
-                    stream.write_all(file_buff).await?;
-                    stream.flush().await?;
-                    let recv_info = proto_common::recv_package(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, None).await?;
-                    if recv_info.errno != 0 {
-                        return Result::Err(io::Error::new(ErrorKind::Other,format!("storage_server返回错误:{}",recv_info.errno)));
-                    }
-                    if recv_info.body.len() <= proto_common::FDFS_GROUP_NAME_MAX_LEN as usize {
-                        return Err(io::Error::new(ErrorKind::Other,"storage_server返回错误:group_name长度不正确!"));
-                    }
+        }
+        Ok(store_path_index)
+    }
+
+    fn check_appender_filename(appender_filename: &str) -> Result<(), io::Error> {
+        if appender_filename.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "appender_filename不能为空"));
+        }
+        Ok(())
+    }
+
+    /**
+     * 协议中文件偏移量按有符号64位整数处理
+     */
+    fn check_file_offset(file_offset: u64) -> Result<(), io::Error> {
+        if file_offset > i64::MAX as u64 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("file_offset:{} 超出范围", file_offset)));
+        }
+        Ok(())
+    }
+
+    /**
+     * 上传普通文件或appender文件，cmd区分上传类型
+     */
+    async fn do_upload_file(cmd: u8, group_name: Option<&str>, file_buff: &[u8], file_ext_name: &str, options: &UploadOptions) -> Result<(String,String), io::Error> {
+        let storage_servers = Self::get_upload_storage_servers(group_name, options.store_routing).await?;
+        Self::upload_file_with_storage_servers(cmd, &storage_servers, file_buff, file_ext_name, options.store_path_index).await
+    }
+
+    /**
+     * 在给定的storage_server中选择一个可连接的上传文件，store_path_index为None时使用storage_server.storage_path
+     */
+    async fn upload_file_with_storage_servers(cmd: u8, storage_servers: &[StorageServer], file_buff: &[u8], file_ext_name: &str, store_path_index: Option<u8>) -> Result<(String,String), io::Error> {
+        let (mut stream, storage_server) = Self::get_upload_connection(storage_servers).await?;
+        let storage_path = Self::get_store_path_index(storage_server, store_path_index)?;
+        Self::send_upload_file(&mut stream, cmd, storage_path, file_buff, file_ext_name).await
+    }
+
+    /**
+     * 在已有连接上发送上传请求并读取结果
+     */
+    async fn send_upload_file(stream: &mut Object<TcpManager>, cmd: u8, storage_path: u8, file_buff: &[u8], file_ext_name: &str) -> Result<(String,String), io::Error> {
+        let whole_pkg = Self::pack_upload_header(cmd, storage_path, file_buff.len() as u64, file_ext_name);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
+        stream.write_all(file_buff).await?;
+        stream.flush().await?;
+        Self::recv_upload_response(stream).await
+    }
+
+    /**
+     * 上传文件后在同一连接上设置元数据（覆盖方式）
+     * 设置元数据失败时删除已上传的文件，避免留下没有元数据的文件
+     */
+    pub async fn upload_file_with_metadata(file_buff: &[u8], file_ext_name: &str, meta_list: &HashMap<String, String>) -> Result<(String,String), io::Error> {
+        // 先打包元数据，charset不支持时不上传
+        let meta_buff = proto_common::pack_metadata(meta_list, &get_settings(None).charset)?;
+        let storage_servers = Self::get_upload_storage_servers(None, StoreRouting::All).await?;
+        let (mut stream, storage_server) = Self::get_upload_connection(&storage_servers).await?;
+        let (group_name, remote_filename) = Self::send_upload_file(&mut stream, proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, storage_server.storage_path, file_buff, file_ext_name).await?;
+
+        let metadata_result = Self::send_set_metadata(&mut stream, &group_name, &remote_filename, &meta_buff, proto_common::STORAGE_SET_METADATA_FLAG_OVERWRITE).await;
+        let Err(e) = metadata_result else {
+            return Ok((group_name, remote_filename));
+        };
+        // 元数据请求可能未完整收发，该连接不再复用；文件刚上传到该storage_server，直接从该storage_server删除
+        discard_connection(stream);
+        match Self::delete_file_from_storage(storage_server, &group_name, &remote_filename).await {
+            Ok(()) => Err(io::Error::new(e.kind(), format!("设置元数据失败，已删除上传的文件:{}", e))),
+            Err(delete_err) => Err(io::Error::new(e.kind(), format!("设置元数据失败:{}，删除上传的文件 {}/{} 失败:{}", e, group_name, remote_filename, delete_err))),
+        }
+    }
+
+    /**
+     * 从reader读取file_size字节并上传，按固定大小分块写入连接，不会在内存中缓存整个文件
+     * reader提前结束时返回UnexpectedEof
+     */
+    pub async fn upload_stream<R>(mut reader: R, file_size: u64, file_ext_name: &str) -> Result<(String,String), io::Error>
+    where
+        R: AsyncRead + Unpin,
+    {
+        Self::check_file_offset(file_size)?;
+        let storage_servers = Self::get_upload_storage_servers(None, StoreRouting::All).await?;
+        let (mut stream, storage_server) = Self::get_upload_connection(&storage_servers).await?;
+        let whole_pkg = Self::pack_upload_header(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, storage_server.storage_path, file_size, file_ext_name);
+        stream.write_all(&whole_pkg).await?;
+        if let Err(e) = Self::copy_bytes(&mut reader, &mut *stream, file_size).await {
+            discard_connection(stream);
+            return Err(e);
+        }
+        Self::recv_upload_response(&mut stream).await
+    }
+
+    /**
+     * 上传本地文件，文件大小从文件元数据中获取
+     * file_ext_name为None时使用文件路径的扩展名；Linux下使用sendfile零拷贝发送文件内容，其他平台分块读取后发送
+     */
+    pub async fn upload_path<P>(path: P, file_ext_name: Option<&str>) -> Result<(String,String), io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file_ext_name = match file_ext_name {
+            Some(ext_name) => ext_name.to_string(),
+            None => path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_string(),
+        };
+        let mut file = tokio::fs::File::open(path).await?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("{} 不是文件", path.display())));
+        }
+        let file_size = metadata.len();
+        Self::check_file_offset(file_size)?;
+
+        let storage_servers = Self::get_upload_storage_servers(None, StoreRouting::All).await?;
+        let (mut stream, storage_server) = Self::get_upload_connection(&storage_servers).await?;
+        let whole_pkg = Self::pack_upload_header(proto_common::STORAGE_PROTO_CMD_UPLOAD_FILE, storage_server.storage_path, file_size, &file_ext_name);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
 
-                    let new_group_name = String::from_utf8_lossy(&recv_info.body[..proto_common::FDFS_GROUP_NAME_MAX_LEN as usize])
-                        .trim_end_matches('\0').to_string();
-                    let remote_filename = String::from_utf8_lossy(&recv_info.body[proto_common::FDFS_GROUP_NAME_MAX_LEN as usize..]).trim().to_string();
-                    return Ok((new_group_name,remote_filename));
+        #[cfg(target_os = "linux")]
+        let send_result = {
+            // sendfile在阻塞线程中执行，发送完成前future被取消时由guard关闭并丢弃连接，不会归还到连接池
+            let guard = SendFileGuard { connection: Some(stream) };
+            let send_result = Self::send_file(guard.stream(), &file, file_size).await;
+            stream = guard.finish();
+            match send_result {
+                // sendfile不可用时尚未发送任何内容，退回到普通读写
+                Err(e) if e.kind() == ErrorKind::Unsupported => {
+                    Self::copy_bytes(&mut file, &mut *stream, file_size).await
                 }
+                send_result => send_result,
+            }
+        };
+        #[cfg(not(target_os = "linux"))]
+        let send_result = Self::copy_bytes(&mut file, &mut *stream, file_size).await;
 
+        if let Err(e) = send_result {
+            discard_connection(stream);
+            return Err(e);
+        }
+        Self::recv_upload_response(&mut stream).await
+    }
 
+    /**
+     * 通过sendfile将file从头开始的len字节直接从内核发送到stream
+     * 第一次调用即返回EINVAL或ENOSYS时表示不支持sendfile，此时尚未发送任何内容，返回ErrorKind::Unsupported
+     */
+    #[cfg(target_os = "linux")]
+    async fn send_file(stream: &tokio::net::TcpStream, file: &tokio::fs::File, len: u64) -> Result<u64, io::Error> {
+        use std::os::fd::AsFd;
+
+        // sendfile读磁盘未命中页缓存时会阻塞线程，放到阻塞线程池中执行，避免占用tokio工作线程；
+        // 复制文件描述符，保证future被取消后阻塞任务使用的描述符仍然有效，此时SendFileGuard会关闭socket使阻塞任务尽快失败退出
+        let socket_fd = stream.as_fd().try_clone_to_owned()?;
+        let file_fd = file.as_fd().try_clone_to_owned()?;
+        let poll_timeout = (get_settings(None).network_timeout * 1000).min(libc::c_int::MAX as u64) as libc::c_int;
+        tokio::task::spawn_blocking(move || Self::send_file_blocking(&socket_fd, &file_fd, len, poll_timeout)).await?
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_file_blocking(socket_fd: &std::os::fd::OwnedFd, file_fd: &std::os::fd::OwnedFd, len: u64, poll_timeout: libc::c_int) -> Result<u64, io::Error> {
+        use std::os::fd::AsRawFd;
+
+        // 单次sendfile最多发送 0x7ffff000 字节
+        const MAX_SENDFILE_SIZE: u64 = 0x7fff_f000;
+        let mut offset: libc::off_t = 0;
+        let mut remain = len;
+        while remain > 0 {
+            let count = remain.min(MAX_SENDFILE_SIZE) as usize;
+            // SAFETY: 两个文件描述符由调用方持有，调用期间均有效，offset 指向栈上的有效变量
+            let n = unsafe { libc::sendfile(socket_fd.as_raw_fd(), file_fd.as_raw_fd(), &mut offset, count) };
+            if n > 0 {
+                remain -= n as u64;
+                continue;
+            }
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, format!("文件提前结束，还剩{}字节未发送", remain)));
+            }
+            let e = io::Error::last_os_error();
+            match e.kind() {
+                // socket为非阻塞模式，等待可写后重试，超过network_timeout仍不可写时返回TimedOut
+                ErrorKind::WouldBlock => {
+                    let mut poll_fd = libc::pollfd { fd: socket_fd.as_raw_fd(), events: libc::POLLOUT, revents: 0 };
+                    // SAFETY: poll_fd 指向栈上的有效变量，数量为1
+                    match unsafe { libc::poll(&mut poll_fd, 1, poll_timeout) } {
+                        0 => return Err(io::Error::new(ErrorKind::TimedOut, format!("等待storage_server可写超时，还剩{}字节未发送", remain))),
+                        n if n < 0 => {
+                            let e = io::Error::last_os_error();
+                            if e.kind() != ErrorKind::Interrupted {
+                                return Err(e);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                ErrorKind::Interrupted => continue,
+                _ if remain == len && matches!(e.raw_os_error(), Some(libc::EINVAL) | Some(libc::ENOSYS)) => {
+                    return Err(io::Error::new(ErrorKind::Unsupported, e));
+                }
+                _ => return Err(e),
             }
+        }
+        Ok(len)
+    }
+
+    /**
+     * 组装上传请求中文件内容之前的部分：header + storage_path + file_size + ext_name
+     */
+    fn pack_upload_header(cmd: u8, storage_path: u8, file_size: u64, file_ext_name: &str) -> Vec<u8> {
+        let ext_name_bs = proto_common::pack_ext_name(file_ext_name);
+        let mut size_bytes = vec![0u8;proto_common::FDFS_PROTO_PKG_LEN_SIZE + 1];
+        let body_len = size_bytes.len() as u64 + proto_common::FDFS_FILE_EXT_NAME_MAX_LEN as u64 + file_size;
+        size_bytes[0] = storage_path;
+        let hex_len_bytes = proto_common::long2buff(file_size);
+        size_bytes[1..1+hex_len_bytes.len()].copy_from_slice(&hex_len_bytes[..hex_len_bytes.len()]);
+        let header = proto_common::pack_header(cmd, body_len,0u8);
+        let mut whole_pkg = vec![0u8; header.len() + size_bytes.len() + ext_name_bs.len()];
+        whole_pkg[..header.len()].copy_from_slice(&header[..header.len()]);
+        whole_pkg[header.len()..header.len()+ size_bytes.len()].copy_from_slice(&size_bytes[..size_bytes.len()]);
+        let offset = header.len() + size_bytes.len();
+        whole_pkg[offset..offset+ext_name_bs.len()].copy_from_slice(&ext_name_bs[..ext_name_bs.len()]);
+        whole_pkg
+    }
+
+    /**
+     * 上传从文件，文件名为 主文件名 + prefix_name + . + file_ext_name
+     * 请求发往主文件所在的源storage_server，返回 (group_name, 从文件名)
+     */
+    pub async fn upload_slave_file(group_name: &str, master_filename: &str, prefix_name: &str, file_ext_name: &str, file_buff: &[u8]) -> Result<(String,String), io::Error> {
+        if master_filename.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "master_filename不能为空"));
+        }
+        if prefix_name.is_empty() || prefix_name.len() > proto_common::FDFS_FILE_PREFIX_MAX_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("prefix_name长度必须在1到{}字节之间", proto_common::FDFS_FILE_PREFIX_MAX_LEN)));
+        }
+        let storage_servers = vec![TrackerServer::get_update_storage(group_name, master_filename).await?];
+        let (mut stream, _) = Self::get_upload_connection(&storage_servers).await?;
+
+        let file_size = file_buff.len();
+        let master_filename_bs = master_filename.as_bytes();
+        let mut prefix_name_bs = vec![0u8; proto_common::FDFS_FILE_PREFIX_MAX_LEN];
+        prefix_name_bs[..prefix_name.len()].copy_from_slice(prefix_name.as_bytes());
+        let ext_name_bs = proto_common::pack_ext_name(file_ext_name);
+        let body_len = 2 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + prefix_name_bs.len() + ext_name_bs.len() + master_filename_bs.len() + file_size;
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_UPLOAD_SLAVE_FILE, body_len as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + body_len - file_size);
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&proto_common::long2buff(master_filename_bs.len() as u64));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(file_size as u64));
+        whole_pkg.extend_from_slice(&prefix_name_bs);
+        whole_pkg.extend_from_slice(&ext_name_bs);
+        whole_pkg.extend_from_slice(master_filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
+        stream.write_all(file_buff).await?;
+        stream.flush().await?;
+        Self::recv_upload_response(&mut stream).await
+    }
+
+    /**
+     * 向tracker_server查询可上传的storage_server，group_name为None时由tracker_server选择group
+     */
+    async fn get_upload_storage_servers(group_name: Option<&str>, store_routing: StoreRouting) -> Result<Vec<StorageServer>, io::Error> {
+        TrackerServer::get_store_storages(group_name, store_routing).await
+            .map_err(|e| io::Error::other(format!("获取storage_server失败!{}", e)))
+    }
+
+    /**
+     * 从随机位置开始依次尝试获取storage_server连接，返回第一个可用的连接及对应的storage_server
+     */
+    async fn get_upload_connection(storage_servers: &[StorageServer]) -> Result<(Object<TcpManager>, &StorageServer), io::Error> {
+        if storage_servers.is_empty(){
+            return Err(io::Error::new(ErrorKind::NotFound,"无可用storage_server"));
+        }
+        let random_start_index = (rand::random::<u16>() as usize) % storage_servers.len();
+        for i in 0..storage_servers.len(){
+            let index = (i + random_start_index) % storage_servers.len();
+            let storage_server = &storage_servers[index];
+            if let Ok(stream) = Self::get_storage_connection(storage_server).await{
+                return Ok((stream, storage_server));
+            }
+        }
+        Err(io::Error::other("所有storage_server获取连接均失败!"))
+    }
+
+    /**
+     * 读取不带响应体的请求结果，storage_server返回错误时转换为io::Error
+     */
+    async fn recv_empty_response(stream: &mut Object<TcpManager>) -> Result<(), io::Error> {
+        let header = proto_common::recv_header(stream, proto_common::STORAGE_PROTO_CMD_RESP, Some(0)).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("storage_server", header.errno));
+        }
+        Ok(())
+    }
+
+    /**
+     * 读取上传类请求的响应，返回 (group_name, remote_filename)
+     */
+    async fn recv_upload_response(stream: &mut Object<TcpManager>) -> Result<(String,String), io::Error> {
+        let recv_info = proto_common::recv_package(stream, proto_common::STORAGE_PROTO_CMD_RESP, None).await?;
+        if recv_info.errno != 0 {
+            return Result::Err(io::Error::other(format!("storage_server返回错误:{}",recv_info.errno)));
+        }
+        if recv_info.body.len() <= proto_common::FDFS_GROUP_NAME_MAX_LEN as usize {
+            return Err(io::Error::other("storage_server返回错误:group_name长度不正确!"));
+        }
+
+        let new_group_name = String::from_utf8_lossy(&recv_info.body[..proto_common::FDFS_GROUP_NAME_MAX_LEN as usize])
+            .trim_end_matches('\0').to_string();
+        let remote_filename = String::from_utf8_lossy(&recv_info.body[proto_common::FDFS_GROUP_NAME_MAX_LEN as usize..]).trim().to_string();
+        Ok((new_group_name,remote_filename))
+    }
+
+    /**
+     * 下载文件，一次性读取整个文件内容到内存，文件不存在时返回ErrorKind::NotFound
+     */
+    pub async fn download_file(group_name: &str, remote_filename: &str) -> Result<Vec<u8>, io::Error> {
+        let storage_server = TrackerServer::get_fetch_storage(group_name, remote_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+        Self::send_download_package(&mut stream, group_name, remote_filename, 0, 0).await?;
+        let header = proto_common::recv_header(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, None).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("storage_server", header.errno));
+        }
+        let mut body = vec![0u8; header.body_len];
+        if let Err(e) = stream.read_exact(&mut body).await {
+            discard_connection(stream);
+            return Err(e);
+        }
+        Ok(body)
+    }
+
+    /**
+     * 删除文件，storage_server返回文件不存在时返回ErrorKind::NotFound
+     * group或源storage_server不存在时tracker_server也返回ENOENT，此时不代表文件已被删除，返回ErrorKind::Other
+     */
+    pub async fn delete_file(group_name: &str, remote_filename: &str) -> Result<(), io::Error> {
+        let storage_server = TrackerServer::get_update_storage(group_name, remote_filename).await
+            .map_err(|e| match e.kind() {
+                ErrorKind::NotFound => io::Error::other(format!("查询源storage_server失败:{}", e)),
+                _ => e,
+            })?;
+        Self::delete_file_from_storage(&storage_server, group_name, remote_filename).await
+    }
+
+    /**
+     * 从指定的storage_server删除文件
+     */
+    async fn delete_file_from_storage(storage_server: &StorageServer, group_name: &str, remote_filename: &str) -> Result<(), io::Error> {
+        let mut stream = Self::get_storage_connection(storage_server).await?;
+        Self::send_package(&mut stream, proto_common::STORAGE_PROTO_CMD_DELETE_FILE, group_name, remote_filename).await?;
+        Self::recv_empty_response(&mut stream).await
+    }
+
+    /**
+     * 设置文件元数据
+     * op_flag为STORAGE_SET_METADATA_FLAG_OVERWRITE时覆盖原有元数据，为STORAGE_SET_METADATA_FLAG_MERGE时与原有元数据合并
+     */
+    pub async fn set_metadata(group_name: &str, remote_filename: &str, meta_list: &HashMap<String, String>, op_flag: u8) -> Result<(), io::Error> {
+        if op_flag != proto_common::STORAGE_SET_METADATA_FLAG_OVERWRITE && op_flag != proto_common::STORAGE_SET_METADATA_FLAG_MERGE {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("op_flag:{} 不正确", op_flag)));
+        }
+        let meta_buff = proto_common::pack_metadata(meta_list, &get_settings(None).charset)?;
+        let storage_server = TrackerServer::get_update_storage(group_name, remote_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+        Self::send_set_metadata(&mut stream, group_name, remote_filename, &meta_buff, op_flag).await
+    }
+
+    /**
+     * 在已有连接上发送设置元数据请求并读取结果，meta_buff为已按charset打包的元数据
+     */
+    async fn send_set_metadata(stream: &mut Object<TcpManager>, group_name: &str, remote_filename: &str, meta_buff: &[u8], op_flag: u8) -> Result<(), io::Error> {
+        let filename_bs = remote_filename.as_bytes();
+        let group_name_bytes = proto_common::pack_group_name(group_name);
+        let body_len = 2 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + 1 + group_name_bytes.len() + filename_bs.len() + meta_buff.len();
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_SET_METADATA, body_len as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + body_len);
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&proto_common::long2buff(filename_bs.len() as u64));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(meta_buff.len() as u64));
+        whole_pkg.push(op_flag);
+        whole_pkg.extend_from_slice(&group_name_bytes);
+        whole_pkg.extend_from_slice(filename_bs);
+        whole_pkg.extend_from_slice(meta_buff);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
+
+        Self::recv_empty_response(stream).await
+    }
+
+    /**
+     * 获取文件元数据
+     */
+    pub async fn get_metadata(group_name: &str, remote_filename: &str) -> Result<HashMap<String, String>, io::Error> {
+        let storage_server = TrackerServer::get_fetch_storage(group_name, remote_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+        Self::send_package(&mut stream, proto_common::STORAGE_PROTO_CMD_GET_METADATA, group_name, remote_filename).await?;
+        let header = proto_common::recv_header(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, None).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("storage_server", header.errno));
+        }
+        let mut meta_buff = vec![0u8; header.body_len];
+        if let Err(e) = stream.read_exact(&mut meta_buff).await {
+            discard_connection(stream);
+            return Err(e);
+        }
+        proto_common::split_metadata(&meta_buff, &get_settings(None).charset)
+    }
 
+    /**
+     * 查询文件信息，calc_crc32为false时storage_server不计算CRC32，返回的crc32为0
+     */
+    pub async fn query_file_info(group_name: &str, remote_filename: &str, calc_crc32: bool) -> Result<FileInfo, io::Error> {
+        let storage_server = TrackerServer::get_fetch_storage(group_name, remote_filename).await?;
+        Self::query_file_info_from_storage(&storage_server, group_name, remote_filename, calc_crc32).await
+    }
+
+    /**
+     * 从指定的storage_server查询文件信息
+     */
+    pub(crate) async fn query_file_info_from_storage(storage_server: &StorageServer, group_name: &str, remote_filename: &str, calc_crc32: bool) -> Result<FileInfo, io::Error> {
+        let mut stream = Self::get_storage_connection(storage_server).await?;
+
+        let flags = if calc_crc32 { 0u8 } else { proto_common::FDFS_QUERY_FINFO_FLAGS_NOT_CALC_CRC32 };
+        let filename_bs = remote_filename.as_bytes();
+        let group_name_bytes = proto_common::pack_group_name(group_name);
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_QUERY_FILE_INFO, (group_name_bytes.len() + filename_bs.len()) as u64, flags);
+        let mut whole_pkg = Vec::with_capacity(header.len() + group_name_bytes.len() + filename_bs.len());
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&group_name_bytes);
+        whole_pkg.extend_from_slice(filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await?;
 
-            Err(io::Error::new(ErrorKind::Other,"所有storage_server获取连接均失败!"))
-        }else{
-            Err(io::Error::new(ErrorKind::Other,"获取storage_server失败!"))
+        let body_len = 3 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + proto_common::FDFS_IPADDR_SIZE;
+        let header = proto_common::recv_header(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, Some(body_len)).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("storage_server", header.errno));
+        }
+        let mut body = vec![0u8; body_len];
+        if let Err(e) = stream.read_exact(&mut body).await {
+            discard_connection(stream);
+            return Err(e);
+        }
+
+        let file_size = proto_common::buff2long(&body, 0);
+        let create_timestamp = proto_common::buff2long(&body, proto_common::FDFS_PROTO_PKG_LEN_SIZE);
+        let crc32 = proto_common::buff2long(&body, 2 * proto_common::FDFS_PROTO_PKG_LEN_SIZE) as u32;
+        let source_ip_addr = String::from_utf8_lossy(&body[3 * proto_common::FDFS_PROTO_PKG_LEN_SIZE..]).trim_end_matches('\0').to_string();
+        Ok(FileInfo::new(file_size, create_timestamp, crc32, &source_ip_addr))
+    }
+
+    /**
+     * 按区间下载文件，并将返回内容分块写入writer，不会在内存中缓存整个文件
+     * file_offset为起始位置，download_bytes为0时表示下载到文件末尾，返回写入的字节数
+     */
+    pub async fn download_file_to_writer<W>(group_name: &str, remote_filename: &str, file_offset: u64, download_bytes: u64, writer: &mut W) -> Result<u64, io::Error>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let storage_server = TrackerServer::get_fetch_storage(group_name, remote_filename).await?;
+        let mut stream = Self::get_storage_connection(&storage_server).await?;
+        Self::send_download_package(&mut stream, group_name, remote_filename, file_offset, download_bytes).await?;
+        let header = proto_common::recv_header(&mut stream, proto_common::STORAGE_PROTO_CMD_RESP, None).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("storage_server", header.errno));
+        }
+        match Self::copy_bytes(&mut *stream, writer, header.body_len as u64).await {
+            Ok(written) => Ok(written),
+            Err(e) => {
+                discard_connection(stream);
+                Err(e)
+            }
+        }
+    }
+
+    /**
+     * 从reader读取len字节并分块写入writer，reader提前结束时返回UnexpectedEof
+     */
+    async fn copy_bytes<R, W>(reader: &mut R, writer: &mut W, len: u64) -> Result<u64, io::Error>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let mut buff = vec![0u8; STREAM_BUFFER_SIZE.min(len as usize)];
+        let mut remain = len;
+        while remain > 0 {
+            let read_len = buff.len().min(remain as usize);
+            let n = reader.read(&mut buff[..read_len]).await?;
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, format!("数据流提前结束，还剩{}字节未读取", remain)));
+            }
+            writer.write_all(&buff[..n]).await?;
+            remain -= n as u64;
         }
+        writer.flush().await?;
+        Ok(len)
+    }
+
+    /**
+     * 获取指定storage_server的连接
+     */
+    pub(crate) async fn get_storage_connection(storage_server: &StorageServer) -> Result<Object<TcpManager>, io::Error> {
+        let host = storage_server.ip.to_string() +":"+ &storage_server.port.to_string();
+        get_connection_pool().get_connection(&host).await
+            .map_err(|e| io::Error::new(ErrorKind::ConnectionRefused, format!("获取storage_server {} 连接失败:{}", host, e)))
+    }
+
+    /**
+     * 发送 header + group_name + filename 格式的请求包
+     */
+    pub(crate) async fn send_package(stream: &mut Object<TcpManager>, cmd: u8, group_name: &str, remote_filename: &str) -> Result<(), io::Error> {
+        let filename_bs = remote_filename.as_bytes();
+        let group_name_bytes = proto_common::pack_group_name(group_name);
+        let header = proto_common::pack_header(cmd, (group_name_bytes.len() + filename_bs.len()) as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + group_name_bytes.len() + filename_bs.len());
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&group_name_bytes);
+        whole_pkg.extend_from_slice(filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await
+    }
+
+    /**
+     * 发送下载请求，file_offset为起始位置，download_bytes为0时表示下载到文件末尾
+     */
+    pub(crate) async fn send_download_package(stream: &mut Object<TcpManager>, group_name: &str, remote_filename: &str, file_offset: u64, download_bytes: u64) -> Result<(), io::Error> {
+        let filename_bs = remote_filename.as_bytes();
+        let group_name_bytes = proto_common::pack_group_name(group_name);
+        let body_len = 2 * proto_common::FDFS_PROTO_PKG_LEN_SIZE + group_name_bytes.len() + filename_bs.len();
+        let header = proto_common::pack_header(proto_common::STORAGE_PROTO_CMD_DOWNLOAD_FILE, body_len as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + body_len);
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&proto_common::long2buff(file_offset));
+        whole_pkg.extend_from_slice(&proto_common::long2buff(download_bytes));
+        whole_pkg.extend_from_slice(&group_name_bytes);
+        whole_pkg.extend_from_slice(filename_bs);
+        stream.write_all(&whole_pkg).await?;
+        stream.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_buff_range() {
+        let file_buff = b"0123456789";
+        assert_eq!(StorageClient::check_buff_range(file_buff, 2, 3).unwrap(), b"234");
+        assert_eq!(StorageClient::check_buff_range(file_buff, 10, 0).unwrap(), b"");
+        assert!(StorageClient::check_buff_range(file_buff, 8, 3).is_err());
+        assert!(StorageClient::check_buff_range(file_buff, usize::MAX, 1).is_err());
+        assert!(StorageClient::check_file_offset(i64::MAX as u64).is_ok());
+        assert!(StorageClient::check_file_offset(i64::MAX as u64 + 1).is_err());
+    }
+
+    #[test]
+    fn test_get_store_path_index() {
+        let storage_server = StorageServer::new("127.0.0.1", 23000, 1);
+        assert_eq!(StorageClient::get_store_path_index(&storage_server, None).unwrap(), 1);
+        assert_eq!(StorageClient::get_store_path_index(&storage_server, Some(5)).unwrap(), 5);
+        let storage_server = storage_server.with_store_path_count(2);
+        assert_eq!(StorageClient::get_store_path_index(&storage_server, Some(1)).unwrap(), 1);
+        assert!(StorageClient::get_store_path_index(&storage_server, Some(2)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_copy_bytes() {
+        let data = vec![7u8; STREAM_BUFFER_SIZE * 2 + 10];
+        let mut writer = Vec::new();
+        let written = StorageClient::copy_bytes(&mut data.as_slice(), &mut writer, data.len() as u64 - 5).await.unwrap();
+        assert_eq!(written, data.len() as u64 - 5);
+        assert_eq!(writer, data[..data.len() - 5]);
+
+        let mut writer = Vec::new();
+        let err = StorageClient::copy_bytes(&mut data.as_slice(), &mut writer, data.len() as u64 + 1).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_send_file() {
+        let data: Vec<u8> = (0..STREAM_BUFFER_SIZE * 3).map(|i| i as u8).collect();
+        let path = std::env::temp_dir().join(format!("fastdfs-client-rs-send-file-{}", std::process::id()));
+        tokio::fs::write(&path, &data).await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let reader = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            socket.read_to_end(&mut received).await.unwrap();
+            received
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let sent = StorageClient::send_file(&stream, &file, data.len() as u64).await.unwrap();
+        drop(stream);
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(sent, data.len() as u64);
+        assert_eq!(reader.await.unwrap(), data);
     }
-}
\ No newline at end of file
+}
@@ -1,16 +1,28 @@
 
-#[derive(Debug)]
+/// 通过 StorageServer::new 构造，store_path_count 只能通过 with_store_path_count 设置
+#[derive(Debug, Clone)]
 pub struct StorageServer {
     pub ip: String,
     pub port: u16,
     pub storage_path: u8,
+    /// storage_server的存储路径数量，未知时为None，上传时用于校验指定的存储路径
+    pub(crate) store_path_count: Option<u8>,
 }
 
 
 impl StorageServer {
     pub fn new(ip:&str,port:u16,storage_path: u8) -> Self {
         StorageServer{
-            ip: ip.to_string(),port,storage_path
+            ip: ip.to_string(),port,storage_path,store_path_count:None
         }
     }
+
+    pub fn with_store_path_count(mut self, store_path_count: u8) -> Self {
+        self.store_path_count = Some(store_path_count);
+        self
+    }
+
+    pub fn store_path_count(&self) -> Option<u8> {
+        self.store_path_count
+    }
 }
\ No newline at end of file
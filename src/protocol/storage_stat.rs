@@ -0,0 +1,194 @@
+use crate::protocol::proto_common;
+use crate::protocol::proto_common::FieldReader;
+use crate::protocol::storage_server::StorageServer;
+
+/// storage_server状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageStatus {
+    Init,
+    WaitSync,
+    Syncing,
+    IpChanged,
+    Deleted,
+    Offline,
+    Online,
+    Active,
+    Recovery,
+    None,
+    Unknown(u8),
+}
+
+impl From<u8> for StorageStatus {
+    fn from(status: u8) -> Self {
+        match status {
+            0 => StorageStatus::Init,
+            1 => StorageStatus::WaitSync,
+            2 => StorageStatus::Syncing,
+            3 => StorageStatus::IpChanged,
+            4 => StorageStatus::Deleted,
+            5 => StorageStatus::Offline,
+            6 => StorageStatus::Online,
+            7 => StorageStatus::Active,
+            9 => StorageStatus::Recovery,
+            99 => StorageStatus::None,
+            _ => StorageStatus::Unknown(status),
+        }
+    }
+}
+
+/// storage_server统计信息，由 TRACKER_PROTO_CMD_SERVER_LIST_STORAGE 返回，时间字段均为unix时间戳（秒）
+#[derive(Debug, Clone)]
+pub struct StorageStat {
+    pub status: StorageStatus,
+    pub id: String,
+    pub ip_addr: String,
+    pub domain_name: String,
+    /// 同步源storage_server的id
+    pub src_id: String,
+    pub version: String,
+    pub join_time: u64,
+    pub up_time: u64,
+    pub total_mb: u64,
+    pub free_mb: u64,
+    pub upload_priority: u32,
+    pub store_path_count: u32,
+    pub subdir_count_per_path: u32,
+    pub current_write_path: u32,
+    pub storage_port: u16,
+    pub storage_http_port: u16,
+
+    pub connection_alloc_count: u32,
+    pub connection_current_count: u32,
+    pub connection_max_count: u32,
+
+    pub total_upload_count: u64,
+    pub success_upload_count: u64,
+    pub total_append_count: u64,
+    pub success_append_count: u64,
+    pub total_modify_count: u64,
+    pub success_modify_count: u64,
+    pub total_truncate_count: u64,
+    pub success_truncate_count: u64,
+    pub total_set_meta_count: u64,
+    pub success_set_meta_count: u64,
+    pub total_delete_count: u64,
+    pub success_delete_count: u64,
+    pub total_download_count: u64,
+    pub success_download_count: u64,
+    pub total_get_meta_count: u64,
+    pub success_get_meta_count: u64,
+    pub total_create_link_count: u64,
+    pub success_create_link_count: u64,
+    pub total_delete_link_count: u64,
+    pub success_delete_link_count: u64,
+    pub total_upload_bytes: u64,
+    pub success_upload_bytes: u64,
+    pub total_append_bytes: u64,
+    pub success_append_bytes: u64,
+    pub total_modify_bytes: u64,
+    pub success_modify_bytes: u64,
+    pub total_download_bytes: u64,
+    pub success_download_bytes: u64,
+    pub total_sync_in_bytes: u64,
+    pub success_sync_in_bytes: u64,
+    pub total_sync_out_bytes: u64,
+    pub success_sync_out_bytes: u64,
+    pub total_file_open_count: u64,
+    pub success_file_open_count: u64,
+    pub total_file_read_count: u64,
+    pub success_file_read_count: u64,
+    pub total_file_write_count: u64,
+    pub success_file_write_count: u64,
+    /// 最后一次上传等源文件变更的时间
+    pub last_source_update: u64,
+    /// 最后一次同步文件变更的时间
+    pub last_sync_update: u64,
+    /// 已同步到的源storage_server的文件时间戳，可用于计算同步延迟
+    pub last_synced_timestamp: u64,
+    pub last_heart_beat_time: u64,
+    pub if_trunk_server: bool,
+}
+
+
+impl StorageStat {
+    /// 单条记录的长度：status + id + ip_addr + domain_name + src_id + version + 10个8字节整数 + 3个4字节连接数 + 42个8字节统计 + if_trunk_server
+    pub const RECORD_SIZE: usize = 1 + 16 + 46 + 128 + 16 + 6 + 10 * 8 + 3 * 4 + 42 * 8 + 1;
+
+    pub(crate) fn from_bytes(bs: &[u8]) -> Self {
+        let mut reader = FieldReader::new(bs);
+        StorageStat {
+            status: StorageStatus::from(reader.read_byte()),
+            id: reader.read_string(proto_common::FDFS_STORAGE_ID_MAX_SIZE),
+            ip_addr: reader.read_string(proto_common::FDFS_IPADDR_SIZE),
+            domain_name: reader.read_string(proto_common::FDFS_DOMAIN_NAME_MAX_SIZE),
+            src_id: reader.read_string(proto_common::FDFS_STORAGE_ID_MAX_SIZE),
+            version: reader.read_string(proto_common::FDFS_VERSION_SIZE),
+            join_time: reader.read_long(),
+            up_time: reader.read_long(),
+            total_mb: reader.read_long(),
+            free_mb: reader.read_long(),
+            upload_priority: reader.read_long() as u32,
+            store_path_count: reader.read_long() as u32,
+            subdir_count_per_path: reader.read_long() as u32,
+            current_write_path: reader.read_long() as u32,
+            storage_port: reader.read_long() as u16,
+            storage_http_port: reader.read_long() as u16,
+
+            connection_alloc_count: reader.read_int(),
+            connection_current_count: reader.read_int(),
+            connection_max_count: reader.read_int(),
+
+            total_upload_count: reader.read_long(),
+            success_upload_count: reader.read_long(),
+            total_append_count: reader.read_long(),
+            success_append_count: reader.read_long(),
+            total_modify_count: reader.read_long(),
+            success_modify_count: reader.read_long(),
+            total_truncate_count: reader.read_long(),
+            success_truncate_count: reader.read_long(),
+            total_set_meta_count: reader.read_long(),
+            success_set_meta_count: reader.read_long(),
+            total_delete_count: reader.read_long(),
+            success_delete_count: reader.read_long(),
+            total_download_count: reader.read_long(),
+            success_download_count: reader.read_long(),
+            total_get_meta_count: reader.read_long(),
+            success_get_meta_count: reader.read_long(),
+            total_create_link_count: reader.read_long(),
+            success_create_link_count: reader.read_long(),
+            total_delete_link_count: reader.read_long(),
+            success_delete_link_count: reader.read_long(),
+            total_upload_bytes: reader.read_long(),
+            success_upload_bytes: reader.read_long(),
+            total_append_bytes: reader.read_long(),
+            success_append_bytes: reader.read_long(),
+            total_modify_bytes: reader.read_long(),
+            success_modify_bytes: reader.read_long(),
+            total_download_bytes: reader.read_long(),
+            success_download_bytes: reader.read_long(),
+            total_sync_in_bytes: reader.read_long(),
+            success_sync_in_bytes: reader.read_long(),
+            total_sync_out_bytes: reader.read_long(),
+            success_sync_out_bytes: reader.read_long(),
+            total_file_open_count: reader.read_long(),
+            success_file_open_count: reader.read_long(),
+            total_file_read_count: reader.read_long(),
+            success_file_read_count: reader.read_long(),
+            total_file_write_count: reader.read_long(),
+            success_file_write_count: reader.read_long(),
+            last_source_update: reader.read_long(),
+            last_sync_update: reader.read_long(),
+            last_synced_timestamp: reader.read_long(),
+            last_heart_beat_time: reader.read_long(),
+            if_trunk_server: reader.read_byte() != 0,
+        }
+    }
+
+    /**
+     * 转换为上传用的StorageServer，并带上存储路径数量用于校验store_path_index
+     */
+    pub fn storage_server(&self, storage_path: u8) -> StorageServer {
+        StorageServer::new(&self.ip_addr, self.storage_port, storage_path)
+            .with_store_path_count(self.store_path_count.min(u8::MAX as u32) as u8)
+    }
+}
@@ -0,0 +1,42 @@
+use crate::protocol::proto_common;
+use crate::protocol::tracker_server::TrackerServer;
+use std::io;
+use std::io::ErrorKind;
+
+/// tracker_server管理操作，会修改集群拓扑，只能通过 TrackerServer::admin() 显式获取后调用
+#[derive(Debug)]
+pub struct TrackerAdmin {
+    _private: (),
+}
+
+impl TrackerServer {
+    /**
+     * 获取tracker_server管理句柄
+     */
+    pub fn admin() -> TrackerAdmin {
+        TrackerAdmin { _private: () }
+    }
+}
+
+impl TrackerAdmin {
+    /**
+     * 从group中删除storage_server（SERVER_DELETE_STORAGE），storage_id可以是storage_server的id或ip
+     * 只能删除离线状态的storage_server，否则tracker_server会返回错误
+     */
+    pub async fn delete_storage(&self, group_name: &str, storage_id: &str) -> Result<(), io::Error> {
+        if storage_id.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "storage_id 不能为空"));
+        }
+        TrackerServer::send_group_storage_request(proto_common::TRACKER_PROTO_CMD_SERVER_DELETE_STORAGE, group_name, storage_id).await?;
+        Ok(())
+    }
+
+    /**
+     * 指定group的trunk_server（SERVER_SET_TRUNK_SERVER），storage_id为None时由tracker_server自动选择
+     * 返回新的trunk_server的id
+     */
+    pub async fn set_trunk_server(&self, group_name: &str, storage_id: Option<&str>) -> Result<String, io::Error> {
+        let body = TrackerServer::send_group_storage_request(proto_common::TRACKER_PROTO_CMD_SERVER_SET_TRUNK_SERVER, group_name, storage_id.unwrap_or_default()).await?;
+        Ok(String::from_utf8_lossy(&body).trim_end_matches('\0').to_string())
+    }
+}
@@ -1,23 +1,56 @@
 use crate::config::settings::get_settings;
+use crate::protocol::group_stat::GroupStat;
 use crate::protocol::pool::connection_manager::TcpManager;
 use crate::protocol::pool::connection_pool::{get_connection_pool};
 use crate::protocol::proto_common;
 use crate::protocol::proto_common::pack_header;
 use crate::protocol::storage_server::StorageServer;
+use crate::protocol::storage_stat::StorageStat;
 use deadpool::managed::Object;
 use std::io;
 use std::io::ErrorKind;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 pub struct TrackerServer;
 
+/// 上传时查询storage_server的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StoreRouting {
+    /// 查询所有可上传的storage_server，由客户端随机选择（QUERY_STORE_*_ALL）
+    #[default]
+    All,
+    /// 由tracker_server按自身的负载均衡策略选出一个storage_server（QUERY_STORE_*_ONE）
+    One,
+}
+
+/// 按文件查询storage_server的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageQuery {
+    /// 查询一个可下载该文件的storage_server（QUERY_FETCH_ONE）
+    FetchOne,
+    /// 查询所有可下载该文件的storage_server（QUERY_FETCH_ALL）
+    FetchAll,
+    /// 查询文件所在的源storage_server，删除、修改文件时使用（QUERY_UPDATE）
+    Update,
+}
+
+impl StorageQuery {
+    pub fn cmd(&self) -> u8 {
+        match self {
+            StorageQuery::FetchOne => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_FETCH_ONE,
+            StorageQuery::FetchAll => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_FETCH_ALL,
+            StorageQuery::Update => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_UPDATE,
+        }
+    }
+}
+
 impl TrackerServer {
     pub async fn get_tracker_server_connection() -> Result<Object<TcpManager>,io::Error>{
         // let tracker_servers = &SETTINGS.read().await.tracker_server;
         let tracker_servers = &get_settings(None).tracker_server;
         let len = tracker_servers.len();
         if len == 0 {
-            return Err(io::Error::new(io::ErrorKind::Other,"tracker_server配置不存在!"));
+            return Err(io::Error::other("tracker_server配置不存在!"));
         }
         let random_start_index = (rand::random::<u16>() as usize) % len;
         for i in 0..len {
@@ -28,54 +61,51 @@ impl TrackerServer {
                 return Ok(connection);
             }
         }
-        Err(io::Error::new(io::ErrorKind::Other,"无可用tracker_server！"))
+        Err(io::Error::other("无可用tracker_server！"))
     }
 
 
 
     pub async fn get_storage_servers(group_name:Option<&str>) -> Result<Vec<StorageServer>, io::Error> {
-        let tracker_server_res = Self::get_tracker_server_connection().await;
-        if tracker_server_res.is_err(){
-            return Err(tracker_server_res.unwrap_err());
-        }
-        let mut tracker_stream = tracker_server_res.unwrap();
+        Self::get_store_storages(group_name, StoreRouting::All).await
+    }
+
+    /**
+     * 查询可上传的storage_server，routing为One时由tracker_server按store_lookup等配置选出一个storage_server
+     * group_name为None或空时由tracker_server选择group
+     */
+    pub async fn get_store_storages(group_name:Option<&str>, routing: StoreRouting) -> Result<Vec<StorageServer>, io::Error> {
+        let mut tracker_stream = Self::get_tracker_server_connection().await?;
         let cmd;
         let out_len;
-        if group_name.is_none() || group_name.unwrap().len() == 0usize {
-            cmd = proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITHOUT_GROUP_ALL;
+        if group_name.is_none_or(|group_name_str| group_name_str.is_empty()) {
+            cmd = match routing {
+                StoreRouting::All => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITHOUT_GROUP_ALL,
+                StoreRouting::One => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITHOUT_GROUP_ONE,
+            };
             out_len = 0u64;
         } else {
-            cmd = proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITH_GROUP_ALL;
+            cmd = match routing {
+                StoreRouting::All => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITH_GROUP_ALL,
+                StoreRouting::One => proto_common::TRACKER_PROTO_CMD_SERVICE_QUERY_STORE_WITH_GROUP_ONE,
+            };
             out_len = proto_common::FDFS_GROUP_NAME_MAX_LEN as u64;
         }
         let header = pack_header(cmd, out_len, 0u8);
-        if let Err(msg) = tracker_stream.write(&header).await {
-            return Err(msg);
-        }
+        tracker_stream.write_all(&header).await?;
 
         if let Some(group_name_str) = group_name  {
-            if group_name_str.len() != 0{
+            if !group_name_str.is_empty(){
                 //将group_name_str根据UTF-8转化为byte数组
-                let bs = group_name_str.as_bytes();
-                let mut group_name_bytes = vec![0u8;proto_common::FDFS_GROUP_NAME_MAX_LEN as usize];
-
-                let group_len = if bs.len() <= proto_common::FDFS_GROUP_NAME_MAX_LEN as usize {
-                    bs.len()
-                } else {
-                    proto_common::FDFS_GROUP_NAME_MAX_LEN as usize
-                };
-                //System.arraycopy(bs, 0, bGroupName, 0, group_len);
-                group_name_bytes[..group_len].copy_from_slice(&bs[..group_len]);
-                if let Err(msg) = tracker_stream.write(&group_name_bytes).await {
-                    return Err(msg);
-                }
+                let group_name_bytes = proto_common::pack_group_name(group_name_str);
+                tracker_stream.write_all(&group_name_bytes).await?;
             }
         }
 
 
         let pkg_info  = proto_common::recv_package(&mut tracker_stream,proto_common::TRACKER_PROTO_CMD_RESP,None).await?;
         if pkg_info.errno != 0 {
-            return Err(io::Error::new(ErrorKind::Other,format!("tracker_server返回错误:{}",pkg_info.errno)));
+            return Err(io::Error::other(format!("tracker_server返回错误:{}",pkg_info.errno)));
         }
 
         if pkg_info.body.len() < proto_common::TRACKER_QUERY_STORAGE_STORE_BODY_LEN {
@@ -84,7 +114,7 @@ impl TrackerServer {
         let ip_port_len = pkg_info.body.len() - (proto_common::FDFS_GROUP_NAME_MAX_LEN as usize + 1);
         let record_length = proto_common::FDFS_IPADDR_SIZE - 1 + proto_common::FDFS_PROTO_PKG_LEN_SIZE;
 
-        if ip_port_len % record_length != 0 {
+        if !ip_port_len.is_multiple_of(record_length) {
             return Err(io::Error::new(ErrorKind::AddrNotAvailable,"无效参数2!"));
         }
 
@@ -95,17 +125,253 @@ impl TrackerServer {
         let mut storage_servers = Vec::with_capacity(server_count);
         let storage_path = pkg_info.body[pkg_info.body.len() - 1];
         let mut offset = proto_common::FDFS_GROUP_NAME_MAX_LEN as usize;
-        for i in 0..server_count {
+        for _ in 0..server_count {
             let x = &pkg_info.body[offset..(offset + proto_common::FDFS_IPADDR_SIZE - 1)];
             let ip = String::from(String::from_utf8_lossy(x).trim_end_matches('\0'));
-            offset += (proto_common::FDFS_IPADDR_SIZE - 1);
+            offset += proto_common::FDFS_IPADDR_SIZE - 1;
             let port = proto_common::buff2long(&pkg_info.body,offset) as u16;
             offset += proto_common::FDFS_PROTO_PKG_LEN_SIZE;
-            storage_servers.push(StorageServer{ip,port,storage_path});
+            storage_servers.push(StorageServer::new(&ip,port,storage_path));
+        }
+        Ok(storage_servers)
+    }
+
+
+    /**
+     * 查询所有group的统计信息（SERVER_LIST_ALL_GROUPS）
+     */
+    pub async fn list_groups() -> Result<Vec<GroupStat>, io::Error> {
+        let mut tracker_stream = Self::get_tracker_server_connection().await?;
+        let header = pack_header(proto_common::TRACKER_PROTO_CMD_SERVER_LIST_ALL_GROUPS, 0, 0u8);
+        tracker_stream.write_all(&header).await?;
+
+        let header = proto_common::recv_header(&mut tracker_stream, proto_common::TRACKER_PROTO_CMD_RESP, None).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("tracker_server", header.errno));
+        }
+        let mut body = vec![0u8; header.body_len];
+        tracker_stream.read_exact(&mut body).await?;
+        if !body.len().is_multiple_of(GroupStat::RECORD_SIZE) {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("tracker_server返回包长度:{} 不是{}的整数倍", body.len(), GroupStat::RECORD_SIZE)));
+        }
+        Ok(body.chunks(GroupStat::RECORD_SIZE).map(GroupStat::from_bytes).collect())
+    }
+
+    /**
+     * 查询group下storage_server的统计信息（SERVER_LIST_STORAGE），storage_id为Some时只查询该storage_server
+     * storage_id可以是storage_server的id或ip
+     */
+    pub async fn list_storages(group_name: &str, storage_id: Option<&str>) -> Result<Vec<StorageStat>, io::Error> {
+        let body = Self::send_group_storage_request(proto_common::TRACKER_PROTO_CMD_SERVER_LIST_STORAGE, group_name, storage_id.unwrap_or_default()).await?;
+        if !body.len().is_multiple_of(StorageStat::RECORD_SIZE) {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("tracker_server返回包长度:{} 不是{}的整数倍", body.len(), StorageStat::RECORD_SIZE)));
+        }
+        Ok(body.chunks(StorageStat::RECORD_SIZE).map(StorageStat::from_bytes).collect())
+    }
+
+    /**
+     * 发送 group_name + storage_id 格式的请求并返回响应包体，storage_id为空时只发送group_name
+     */
+    pub(crate) async fn send_group_storage_request(cmd: u8, group_name: &str, storage_id: &str) -> Result<Vec<u8>, io::Error> {
+        proto_common::check_group_name(group_name)?;
+        let storage_id_bs = storage_id.as_bytes();
+        let storage_id_len = storage_id_bs.len().min(proto_common::FDFS_IPADDR_SIZE - 1);
+        let group_name_bytes = proto_common::pack_group_name(group_name);
+        let header = pack_header(cmd, (group_name_bytes.len() + storage_id_len) as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + group_name_bytes.len() + storage_id_len);
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&group_name_bytes);
+        whole_pkg.extend_from_slice(&storage_id_bs[..storage_id_len]);
+
+        let mut tracker_stream = Self::get_tracker_server_connection().await?;
+        tracker_stream.write_all(&whole_pkg).await?;
+        let header = proto_common::recv_header(&mut tracker_stream, proto_common::TRACKER_PROTO_CMD_RESP, None).await?;
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("tracker_server", header.errno));
+        }
+        let mut body = vec![0u8; header.body_len];
+        tracker_stream.read_exact(&mut body).await?;
+        Ok(body)
+    }
+
+    /**
+     * 查询可下载指定文件的storage_server（QUERY_FETCH_ONE）
+     */
+    pub async fn get_fetch_storage(group_name: &str, filename: &str) -> Result<StorageServer, io::Error> {
+        let mut storage_servers = Self::query_storages_by_file(StorageQuery::FetchOne, group_name, filename).await?;
+        Ok(storage_servers.remove(0))
+    }
+
+    /**
+     * 查询所有可下载指定文件的storage_server（QUERY_FETCH_ALL）
+     */
+    pub async fn get_fetch_storages(group_name: &str, filename: &str) -> Result<Vec<StorageServer>, io::Error> {
+        Self::query_storages_by_file(StorageQuery::FetchAll, group_name, filename).await
+    }
+
+    /**
+     * 查询文件所在的源storage_server，删除、修改文件时使用（QUERY_UPDATE）
+     */
+    pub async fn get_update_storage(group_name: &str, filename: &str) -> Result<StorageServer, io::Error> {
+        let mut storage_servers = Self::query_storages_by_file(StorageQuery::Update, group_name, filename).await?;
+        Ok(storage_servers.remove(0))
+    }
+
+    /**
+     * 按文件ID（group_name/remote_filename）查询storage_server
+     */
+    pub async fn query_storages_by_file_id(query: StorageQuery, file_id: &str) -> Result<Vec<StorageServer>, io::Error> {
+        let (group_name, filename) = proto_common::split_file_id(file_id)?;
+        Self::query_storages_by_file(query, group_name, filename).await
+    }
+
+    /**
+     * 按group_name和filename向tracker_server查询storage_server，返回的列表至少包含一个storage_server，storage_path无意义，固定为0
+     * group或文件的源storage_server不存在时返回ErrorKind::NotFound
+     */
+    pub async fn query_storages_by_file(query: StorageQuery, group_name: &str, filename: &str) -> Result<Vec<StorageServer>, io::Error> {
+        proto_common::check_group_name(group_name)?;
+        if filename.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "filename不能为空"));
+        }
+        let mut tracker_stream = Self::get_tracker_server_connection().await?;
+        let filename_bs = filename.as_bytes();
+        let group_name_bytes = proto_common::pack_group_name(group_name);
+        let header = pack_header(query.cmd(), (group_name_bytes.len() + filename_bs.len()) as u64, 0u8);
+        let mut whole_pkg = Vec::with_capacity(header.len() + group_name_bytes.len() + filename_bs.len());
+        whole_pkg.extend_from_slice(&header);
+        whole_pkg.extend_from_slice(&group_name_bytes);
+        whole_pkg.extend_from_slice(filename_bs);
+        tracker_stream.write_all(&whole_pkg).await?;
+
+        let header = proto_common::recv_header(&mut tracker_stream, proto_common::TRACKER_PROTO_CMD_RESP, None).await?;
+        if header.errno == proto_common::ERR_NO_ENOENT {
+            return Err(io::Error::new(ErrorKind::NotFound, format!("tracker_server返回错误:{}，group:{} 或文件的源storage_server不存在", header.errno, group_name)));
+        }
+        if header.errno != 0 {
+            return Err(proto_common::errno_error("tracker_server", header.errno));
+        }
+        let mut body = vec![0u8; header.body_len];
+        tracker_stream.read_exact(&mut body).await?;
+        Self::parse_fetch_storages(&body)
+    }
+
+    /**
+     * 解析QUERY_FETCH_ONE、QUERY_FETCH_ALL、QUERY_UPDATE的响应
+     * 格式为 group_name + ip + port + 其他ip...，其他storage_server的端口与第一个相同
+     */
+    fn parse_fetch_storages(body: &[u8]) -> Result<Vec<StorageServer>, io::Error> {
+        if body.len() < proto_common::TRACKER_QUERY_STORAGE_FETCH_BODY_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("tracker_server返回包长度不正确:{}", body.len())));
+        }
+        let ip_len = proto_common::FDFS_IPADDR_SIZE - 1;
+        if !(body.len() - proto_common::TRACKER_QUERY_STORAGE_FETCH_BODY_LEN).is_multiple_of(ip_len) {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("tracker_server返回包长度不正确:{}", body.len())));
+        }
+        let server_count = 1 + (body.len() - proto_common::TRACKER_QUERY_STORAGE_FETCH_BODY_LEN) / ip_len;
+
+        //第一条记录为 ip + port，之后的记录只有ip，端口与第一条相同
+        let mut offset = proto_common::FDFS_GROUP_NAME_MAX_LEN as usize;
+        let ip = String::from_utf8_lossy(&body[offset..offset + ip_len]).trim_end_matches('\0').to_string();
+        offset += ip_len;
+        let port = proto_common::buff2long(body, offset) as u16;
+        offset += proto_common::FDFS_PROTO_PKG_LEN_SIZE;
+
+        let mut storage_servers = Vec::with_capacity(server_count);
+        storage_servers.push(StorageServer::new(&ip, port, 0));
+        for _ in 1..server_count {
+            let ip = String::from_utf8_lossy(&body[offset..offset + ip_len]).trim_end_matches('\0').to_string();
+            offset += ip_len;
+            storage_servers.push(StorageServer::new(&ip, port, 0));
         }
         Ok(storage_servers)
     }
+}
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-}
\ No newline at end of file
+    #[test]
+    fn test_parse_fetch_storages() {
+        let ip_len = proto_common::FDFS_IPADDR_SIZE - 1;
+        let mut body = proto_common::pack_group_name("group1");
+        for (i, ip) in ["192.168.1.100", "192.168.1.101", "192.168.1.102"].iter().enumerate() {
+            let mut ip_bytes = vec![0u8; ip_len];
+            ip_bytes[..ip.len()].copy_from_slice(ip.as_bytes());
+            body.extend_from_slice(&ip_bytes);
+            if i == 0 {
+                body.extend_from_slice(&proto_common::long2buff(23000));
+            }
+        }
+        let storage_servers = TrackerServer::parse_fetch_storages(&body).unwrap();
+        assert_eq!(storage_servers.len(), 3);
+        assert_eq!(storage_servers[2].ip, "192.168.1.102");
+        assert!(storage_servers.iter().all(|storage_server| storage_server.port == 23000));
+
+        assert!(TrackerServer::parse_fetch_storages(&body[..body.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_group_stat_from_bytes() {
+        let mut body = vec![0u8; proto_common::FDFS_GROUP_NAME_MAX_LEN as usize + 1];
+        body[..6].copy_from_slice(b"group1");
+        for value in [102400u64, 51200, 1024, 2, 23000, 8888, 1, 0, 2, 256, 7] {
+            body.extend_from_slice(&proto_common::long2buff(value));
+        }
+        assert_eq!(body.len(), GroupStat::RECORD_SIZE);
+
+        let group_stat = GroupStat::from_bytes(&body);
+        assert_eq!(group_stat.group_name, "group1");
+        assert_eq!(group_stat.total_mb, 102400);
+        assert_eq!(group_stat.free_mb, 51200);
+        assert_eq!(group_stat.storage_port, 23000);
+        assert_eq!(group_stat.storage_http_port, 8888);
+        assert_eq!(group_stat.store_path_count, 2);
+        assert_eq!(group_stat.current_trunk_file_id, 7);
+    }
+
+    #[test]
+    fn test_storage_stat_from_bytes() {
+        fn pack_string(value: &str, len: usize) -> Vec<u8> {
+            let mut bs = vec![0u8; len];
+            bs[..value.len()].copy_from_slice(value.as_bytes());
+            bs
+        }
+
+        let mut body = vec![7u8];
+        body.extend(pack_string("100001", proto_common::FDFS_STORAGE_ID_MAX_SIZE));
+        body.extend(pack_string("192.168.1.100", proto_common::FDFS_IPADDR_SIZE));
+        body.extend(pack_string("", proto_common::FDFS_DOMAIN_NAME_MAX_SIZE));
+        body.extend(pack_string("100002", proto_common::FDFS_STORAGE_ID_MAX_SIZE));
+        body.extend(pack_string("6.12", proto_common::FDFS_VERSION_SIZE));
+        for value in [1700000000u64, 1700000100, 102400, 51200, 10, 2, 256, 1, 23000, 8888] {
+            body.extend_from_slice(&proto_common::long2buff(value));
+        }
+        for value in [256u32, 3, 12] {
+            body.extend_from_slice(&value.to_be_bytes());
+        }
+        for value in 1..=42u64 {
+            body.extend_from_slice(&proto_common::long2buff(value));
+        }
+        body.push(1);
+        assert_eq!(body.len(), StorageStat::RECORD_SIZE);
+
+        let storage_stat = StorageStat::from_bytes(&body);
+        assert_eq!(storage_stat.status, crate::protocol::storage_stat::StorageStatus::Active);
+        assert_eq!(storage_stat.id, "100001");
+        assert_eq!(storage_stat.ip_addr, "192.168.1.100");
+        assert_eq!(storage_stat.src_id, "100002");
+        assert_eq!(storage_stat.version, "6.12");
+        assert_eq!(storage_stat.store_path_count, 2);
+        assert_eq!(storage_stat.storage_port, 23000);
+        assert_eq!(storage_stat.connection_max_count, 12);
+        assert_eq!(storage_stat.total_upload_count, 1);
+        assert_eq!(storage_stat.success_sync_out_bytes, 32);
+        assert_eq!(storage_stat.last_synced_timestamp, 41);
+        assert_eq!(storage_stat.last_heart_beat_time, 42);
+        assert!(storage_stat.if_trunk_server);
+        assert_eq!(storage_stat.storage_server(1).store_path_count, Some(2));
+    }
+}
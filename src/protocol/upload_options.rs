@@ -0,0 +1,28 @@
+use crate::protocol::tracker_server::StoreRouting;
+
+/// 上传选项
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// 指定存储路径序号，为None时使用tracker_server返回的存储路径；
+    /// 存储路径数量未知，不校验序号是否越界，越界时由storage_server返回错误
+    pub store_path_index: Option<u8>,
+    /// 查询可上传storage_server的方式，默认查询全部后随机选择
+    pub store_routing: StoreRouting,
+}
+
+
+impl UploadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn store_path_index(mut self, store_path_index: u8) -> Self {
+        self.store_path_index = Some(store_path_index);
+        self
+    }
+
+    pub fn store_routing(mut self, store_routing: StoreRouting) -> Self {
+        self.store_routing = store_routing;
+        self
+    }
+}